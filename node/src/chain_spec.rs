@@ -1,4 +1,4 @@
-use sp_core::{Pair, Public, sr25519, H256};
+use sp_core::{Pair, Public, ed25519, sr25519, H256};
 use utxo_runtime::{
 	AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig,
 	SudoConfig, SystemConfig, WASM_BINARY, Signature, UtxoConfig,
@@ -68,10 +68,10 @@ impl Alternative {
 						get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 						get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
 					],
-					// genesis set of public key that own OTXOs
+					// genesis set of accounts that own UTXOs
 					vec![
-						get_from_seed::<sr25519::Public>("Alice"),
-						get_from_seed::<sr25519::Public>("Bob")
+						get_account_id_from_seed::<sr25519::Public>("Alice"),
+						get_account_id_from_seed::<sr25519::Public>("Bob")
 					],
 					true,
 				),
@@ -104,10 +104,11 @@ impl Alternative {
 						get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
 						get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
 					],
-					// genesis set of public key that own OTXOs
+					// genesis set of accounts that own UTXOs
 					vec![
-						get_from_seed::<sr25519::Public>("Alice"),
-						get_from_seed::<sr25519::Public>("Bob")
+						get_account_id_from_seed::<sr25519::Public>("Alice"),
+						get_account_id_from_seed::<sr25519::Public>("Bob"),
+						get_account_id_from_seed::<ed25519::Public>("Charlie"),
 					],
 					true,
 				),
@@ -133,7 +134,7 @@ fn testnet_genesis(
 	initial_authorities: Vec<(AuraId, GrandpaId)>,
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
-	endowed_utox: Vec<sr25519::Public>,
+	endowed_utox: Vec<AccountId>,
 	_enable_println: bool
 ) -> GenesisConfig {
 	
@@ -160,8 +161,8 @@ fn testnet_genesis(
 				.map(|x| 
 						utxo::TransactionOutput {
 							value: 100 as utxo::Value,
-							pubkey: H256::from_slice(x.as_slice()),
-
+							lock: utxo::Lock::Key(H256::from_slice(x.as_ref())),
+							lock_until: None,
 						}
 					)
 					.collect()
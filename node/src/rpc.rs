@@ -0,0 +1,129 @@
+//! UTXO specific RPC methods.
+//!
+//! Exposes the `utxo_*` namespace so wallets can read decoded UTXO state
+//! instead of computing `UtxoStore` storage keys and decoding raw SCALE bytes.
+
+use std::sync::Arc;
+use std::marker::PhantomData;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{H256, U256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use utxo_runtime::utxo::{BlockNumber, Lock, TransactionLocation, TransactionOutput, UtxoApi as UtxoRuntimeApi};
+
+/// `TransactionOutput` as returned over RPC. Values are `u128`, which JSON
+/// numbers cannot hold exactly above 2^53, so they are hex encoded `U256`s.
+#[derive(PartialEq, Eq, Clone, Serialize, Deserialize, Debug)]
+pub struct Output {
+	pub value: U256,
+	pub lock: Lock,
+	pub lock_until: Option<BlockNumber>,
+}
+
+impl From<TransactionOutput> for Output {
+	fn from(output: TransactionOutput) -> Self {
+		Output { value: output.value.into(), lock: output.lock, lock_until: output.lock_until }
+	}
+}
+
+/// UTXO RPC methods.
+#[rpc]
+pub trait UtxoApi<BlockHash> {
+	/// Returns the unspent output stored under `outpoint`, if any.
+	#[rpc(name = "utxo_getUtxo")]
+	fn get_utxo(&self, outpoint: H256, at: Option<BlockHash>) -> Result<Option<Output>>;
+
+	/// Returns the outpoints of every unspent output whose lock involves `pubkey`.
+	#[rpc(name = "utxo_listByPubkey")]
+	fn list_by_pubkey(&self, pubkey: H256, at: Option<BlockHash>) -> Result<Vec<H256>>;
+
+	/// Returns the total value of the unspent outputs `pubkey` can spend alone,
+	/// leaving out shared and time locked outputs. Hex encoded, see `Output`.
+	#[rpc(name = "utxo_balance")]
+	fn balance(&self, pubkey: H256, at: Option<BlockHash>) -> Result<U256>;
+
+	/// Returns the block and extrinsic index that included the transaction
+	/// with id `txid`, if it was included recently enough to still be indexed.
+	#[rpc(name = "utxo_getTransactionLocation")]
+	fn get_transaction_location(&self, txid: H256, at: Option<BlockHash>) -> Result<Option<TransactionLocation>>;
+}
+
+/// Implementation of the UTXO RPC methods backed by the `UtxoApi` runtime API.
+pub struct Utxo<C, B> {
+	client: Arc<C>,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> Utxo<C, B> {
+	/// Create new `Utxo` with the given reference to the client.
+	pub fn new(client: Arc<C>) -> Self {
+		Utxo { client, _marker: Default::default() }
+	}
+}
+
+impl<C, Block> UtxoApi<<Block as BlockT>::Hash> for Utxo<C, Block>
+where
+	Block: BlockT,
+	C: Send + Sync + 'static,
+	C: ProvideRuntimeApi,
+	C: HeaderBackend<Block>,
+	C::Api: UtxoRuntimeApi<Block>,
+{
+	fn get_utxo(&self, outpoint: H256, at: Option<<Block as BlockT>::Hash>) -> Result<Option<Output>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.utxo(&at, outpoint).map(|output| output.map(Output::from)).map_err(runtime_error)
+	}
+
+	fn list_by_pubkey(&self, pubkey: H256, at: Option<<Block as BlockT>::Hash>) -> Result<Vec<H256>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.outpoints_of(&at, pubkey).map_err(runtime_error)
+	}
+
+	fn balance(&self, pubkey: H256, at: Option<<Block as BlockT>::Hash>) -> Result<U256> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.balance_of(&at, pubkey).map(U256::from).map_err(runtime_error)
+	}
+
+	fn get_transaction_location(
+		&self,
+		txid: H256,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<TransactionLocation>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.transaction_location(&at, txid).map_err(runtime_error)
+	}
+}
+
+/// Instantiate the RPC extensions exposed by this node.
+pub fn create<C, Block, M>(client: Arc<C>) -> jsonrpc_core::IoHandler<M>
+where
+	Block: BlockT,
+	C: Send + Sync + 'static,
+	C: ProvideRuntimeApi,
+	C: HeaderBackend<Block>,
+	C::Api: UtxoRuntimeApi<Block>,
+	M: jsonrpc_core::Metadata + Default,
+{
+	let mut io = jsonrpc_core::IoHandler::default();
+	io.extend_with(UtxoApi::to_delegate(Utxo::new(client)));
+	io
+}
+
+fn runtime_error(e: impl std::fmt::Debug) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(1),
+		message: "Unable to query UTXO state.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
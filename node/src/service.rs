@@ -12,6 +12,9 @@ pub use sc_executor::NativeExecutor;
 use sp_consensus_aura::sr25519::{AuthorityPair as AuraPair};
 use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider, StorageAndProofProvider};
 
+/// RPC extensions exposed by both full and light clients.
+pub type RpcExtension = jsonrpc_core::IoHandler<sc_rpc::Metadata>;
+
 // Our native executor instance.
 native_executor_instance!(
 	pub Executor,
@@ -62,6 +65,9 @@ macro_rules! new_full_start {
 				import_setup = Some((grandpa_block_import, grandpa_link));
 
 				Ok(import_queue)
+			})?
+			.with_rpc_extensions(|builder| -> Result<crate::service::RpcExtension, _> {
+				Ok(crate::rpc::create(builder.client().clone()))
 			})?;
 
 		(builder, import_setup, inherent_data_providers)
@@ -230,5 +236,127 @@ pub fn new_light(config: Configuration)
 			let provider = client as Arc<dyn StorageAndProofProvider<_, _>>;
 			Ok(Arc::new(GrandpaFinalityProofProvider::new(backend, provider)) as _)
 		})?
+		.with_rpc_extensions(|builder| -> Result<RpcExtension, _> {
+			Ok(crate::rpc::create(builder.client().clone()))
+		})?
 		.build()
 }
+
+/// Tests for this module
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use futures::{executor::block_on, future::{ready, Ready}};
+	use sc_transaction_pool::{BasicPool, txpool::{self, error::Error as PoolError}};
+	use sp_core::{Encode, H256, Pair, sr25519};
+	use sp_runtime::{BuildStorage, Storage, generic::BlockId, traits::{BlakeTwo256, Hash}};
+	use sp_runtime::transaction_validity::TransactionValidity;
+	use sp_transaction_pool::{InPoolTransaction, TransactionPool};
+	use utxo_runtime::{Block, Call, Executive, GenesisConfig, System, UncheckedExtrinsic, UtxoConfig, Utxo, utxo};
+
+	/// Run `f` natively against the `genesis` state as of block 1, from which spends are accepted
+	fn at_block_one<R>(genesis: &Storage, f: impl FnOnce() -> R) -> R {
+		sp_io::TestExternalities::new(genesis.clone()).execute_with(|| {
+			System::set_block_number(1);
+			f()
+		})
+	}
+
+	/// Validates transactions against the genesis state, see `at_block_one`
+	struct TestApi {
+		genesis: Storage,
+	}
+
+	impl txpool::ChainApi for TestApi {
+		type Block = Block;
+		type Hash = H256;
+		type Error = PoolError;
+		type ValidationFuture = Ready<Result<TransactionValidity, PoolError>>;
+		type BodyFuture = Ready<Result<Option<Vec<UncheckedExtrinsic>>, PoolError>>;
+
+		fn validate_transaction(&self, _at: &BlockId<Block>, uxt: UncheckedExtrinsic) -> Self::ValidationFuture {
+			ready(Ok(at_block_one(&self.genesis, || Executive::validate_transaction(uxt))))
+		}
+
+		fn block_id_to_number(&self, at: &BlockId<Block>) -> Result<Option<txpool::NumberFor<Self>>, PoolError> {
+			Ok(match at {
+				BlockId::Number(number) => Some(*number),
+				BlockId::Hash(_) => Some(0),
+			})
+		}
+
+		fn block_id_to_hash(&self, _at: &BlockId<Block>) -> Result<Option<txpool::BlockHash<Self>>, PoolError> {
+			Ok(Some(Default::default()))
+		}
+
+		fn hash_and_length(&self, uxt: &UncheckedExtrinsic) -> (H256, usize) {
+			(BlakeTwo256::hash_of(uxt), uxt.encode().len())
+		}
+
+		fn block_body(&self, _at: &BlockId<Block>) -> Self::BodyFuture {
+			ready(Ok(None))
+		}
+	}
+
+	fn genesis(owner: H256) -> Storage {
+		GenesisConfig {
+			system: None,
+			aura: None,
+			grandpa: None,
+			balances: None,
+			sudo: None,
+			utxo: Some(UtxoConfig {
+				genesis_utxo: vec![utxo::TransactionOutput {
+					value: 100,
+					lock: utxo::Lock::Key(owner),
+					lock_until: None,
+				}],
+			}),
+		}.build_storage().unwrap()
+	}
+
+	/// Alice pays Bob `outpoint` of `genesis`, less `fee`, in `outputs` + 1 outputs
+	fn payment(genesis: &Storage, outpoint: H256, fee: utxo::Value, outputs: u8) -> UncheckedExtrinsic {
+		let alice = sr25519::Pair::from_string("//Alice", None).unwrap();
+		let mut transaction = utxo::Transaction {
+			inputs: vec![utxo::TransactionInput { outpoint, ..Default::default() }],
+			outputs: (0..=outputs).map(|i| utxo::TransactionOutput {
+				value: if i == 0 { 100 - fee - outputs as utxo::Value } else { 1 },
+				lock: utxo::Lock::Key(H256::repeat_byte(i)),
+				lock_until: None,
+			}).collect(),
+		};
+		let payload = at_block_one(genesis, || Utxo::get_simple_transaction(&transaction));
+		transaction.inputs[0].sigscript = vec![alice.sign(&payload).0.to_vec()];
+		UncheckedExtrinsic::new_unsigned(Call::Utxo(utxo::Call::spend(transaction)))
+	}
+
+	#[test]
+	fn pool_replaces_spends_paying_more() {
+		let owner = H256::from(sr25519::Pair::from_string("//Alice", None).unwrap().public());
+		let storage = genesis(owner);
+		let outpoint = at_block_one(&storage, || Utxo::outpoints_of(&owner)[0]);
+		let pool = BasicPool::new(Default::default(), Arc::new(TestApi { genesis: storage.clone() }));
+
+		let payment = |fee, outputs| payment(&storage, outpoint, fee, outputs);
+		let in_pool = || pool.ready().map(|tx| tx.data().clone()).collect::<Vec<_>>();
+		let at = BlockId::number(0);
+
+		let stuck = payment(20, 40);
+		block_on(pool.submit_one(&at, stuck.clone())).unwrap();
+		assert_eq!(in_pool(), vec![stuck.clone()]);
+
+		// a higher fee at a lower fee rate does not replace it
+		assert!(block_on(pool.submit_one(&at, payment(21, 70))).is_err());
+		assert_eq!(in_pool(), vec![stuck.clone()]);
+
+		// a higher fee at a higher fee rate does
+		let bumped = payment(30, 0);
+		block_on(pool.submit_one(&at, bumped.clone())).unwrap();
+		assert_eq!(in_pool(), vec![bumped.clone()]);
+
+		// and the replaced spend cannot come back
+		assert!(block_on(pool.submit_one(&at, stuck)).is_err());
+		assert_eq!(in_pool(), vec![bumped]);
+	}
+}
@@ -0,0 +1,68 @@
+//! Benchmarks of the UTXO pallet, built with the `runtime-benchmarks` feature.
+//!
+//! `spend` is measured over the components `spend_weight` grows with: its
+//! inputs, its outputs and the length of a preimage revealed by its first
+//! input. The results are returned by the runtime's `Benchmark` API.
+
+use frame_benchmarking::benchmarks;
+use sp_core::{H256, crypto::KeyTypeId};
+use sp_runtime::traits::{BlakeTwo256, Hash, One};
+use sp_std::prelude::*;
+use system::RawOrigin;
+use crate::script::Script;
+use crate::utxo::*;
+
+// Key type of the key the benchmarked outputs are locked to
+const BENCHMARK: KeyTypeId = KeyTypeId(*b"bnch");
+// Longest preimage the first input reveals
+const MAX_PREIMAGE: u32 = 10_000;
+
+benchmarks! {
+	_ { }
+
+	spend {
+		let i in 1 .. MAX_INPUTS as u32;
+		let o in 1 .. MAX_OUTPUTS as u32;
+		let l in 0 .. MAX_PREIMAGE;
+
+		// spends are only accepted once the genesis hash is known
+		system::Module::<T>::set_block_number(One::one());
+		let public = sp_io::crypto::sr25519_generate(BENCHMARK, None);
+		let key = H256::from(public);
+
+		// the first input is locked to a key and an `l` byte preimage, every other one to the key alone
+		let preimage = vec![0u8; l as usize];
+		let script = Script::And(
+			Box::new(Script::Key(key)),
+			Box::new(Script::Blake2Preimage(H256::from(sp_io::hashing::blake2_256(&preimage)))),
+		);
+		let inputs = (0..i).map(|index| {
+			let outpoint = BlakeTwo256::hash_of(&index);
+			let lock = match index {
+				0 => Lock::ScriptHash(BlakeTwo256::hash_of(&script)),
+				_ => Lock::Key(key),
+			};
+			Module::<T>::insert_utxo(outpoint, TransactionOutput { value: MAX_OUTPUTS as Value, lock, lock_until: None });
+			TransactionInput {
+				outpoint,
+				script: if index == 0 { Some(script.clone()) } else { None },
+				..Default::default()
+			}
+		}).collect();
+		let outputs = (0..o).map(|index| TransactionOutput {
+			value: 1,
+			lock: Lock::Key(BlakeTwo256::hash_of(&(key, index))),
+			lock_until: None,
+		}).collect();
+
+		let mut transaction = Transaction { inputs, outputs };
+		let payload = Module::<T>::get_simple_transaction(&transaction);
+		let signature = sp_io::crypto::sr25519_sign(BENCHMARK, &public, &payload)
+			.expect("the key was generated in the keystore above; qed")
+			.0.to_vec();
+		for input in transaction.inputs.iter_mut() {
+			input.sigscript = vec![signature.clone()];
+		}
+		transaction.inputs[0].sigscript.push(preimage);
+	}: _(RawOrigin::None, transaction)
+}
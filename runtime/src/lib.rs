@@ -14,8 +14,7 @@ use sp_runtime::{
 	ApplyExtrinsicResult,
 	transaction_validity::{
 		TransactionValidity,
-		TransactionValidityError,
-		InvalidTransaction,
+		ValidTransaction,
 	},
 	generic, create_runtime_str,
 	impl_opaque_keys, MultiSignature,
@@ -24,6 +23,7 @@ use sp_runtime::traits::{
 	BlakeTwo256, Block as BlockT, IdentityLookup, Verify, ConvertInto, IdentifyAccount
 };
 use sp_api::impl_runtime_apis;
+use frame_support::traits::FindAuthor;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use grandpa::AuthorityList as GrandpaAuthorityList;
 use grandpa::fg_primitives;
@@ -72,6 +72,13 @@ pub type DigestItem = generic::DigestItem<Hash>;
 /// The UTXO pallet in `./utxo.rs`
 pub mod utxo;
 
+/// Spending condition scripts of the UTXO pallet in `./script.rs`
+pub mod script;
+
+/// Benchmarks of the UTXO pallet in `./benchmarking.rs`
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
 /// of data like extrinsics, allowing for them to continue syncing the network through upgrades
@@ -101,7 +108,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("utxo"),
 	impl_name: create_runtime_str!("utxo"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 2,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 };
@@ -227,8 +234,53 @@ impl sudo::Trait for Runtime {
 	type Call = Call;
 }
 
+parameter_types! {
+	/// Value minted per block before the first halving
+	pub const InitialSubsidy: utxo::Value = 50;
+	/// Blocks between subsidy halvings, about a year
+	pub const HalvingInterval: utxo::BlockNumber = 365 * DAYS as utxo::BlockNumber;
+	/// Rewards become spendable after ten minutes of blocks, well past GRANDPA finality
+	pub const CoinbaseMaturity: utxo::BlockNumber = 10 * MINUTES as utxo::BlockNumber;
+	/// Least fee per million weight, which rejects transactions without fee
+	pub const MinFeeRate: utxo::Value = 1;
+	/// Keep the transaction index for a day of blocks
+	pub const TxIndexDepth: utxo::BlockNumber = DAYS as utxo::BlockNumber;
+}
+
+/// Aura authorities, in the key format of the UTXO pallet
+pub struct AuraAuthorities;
+
+impl utxo::AuthoritiesProvider for AuraAuthorities {
+	fn authorities() -> Vec<Hash> {
+		Aura::authorities().iter().map(|x| {
+			let r: &sp_core::sr25519::Public = x.as_ref();
+			r.0.into()
+		}).collect()
+	}
+}
+
+/// Author of the current block, found from its Aura pre-runtime digest
+pub struct AuraAuthor;
+
+impl utxo::BlockAuthor for AuraAuthor {
+	fn block_author() -> Option<Hash> {
+		let digest = System::digest();
+		let pre_runtime_digests = digest.logs.iter().filter_map(|d| d.as_pre_runtime());
+		let index = Aura::find_author(pre_runtime_digests)?;
+		<AuraAuthorities as utxo::AuthoritiesProvider>::authorities().get(index as usize).cloned()
+	}
+}
+
 impl utxo::Trait for Runtime {
 	type Event = Event;
+	type TxIndexDepth = TxIndexDepth;
+	type Authorities = AuraAuthorities;
+	type BlockAuthor = AuraAuthor;
+	/// Also available: `utxo::EvenSplit` and `utxo::AuthorAndTreasury`
+	type RewardPolicy = utxo::AuthorTakesAll;
+	type Subsidy = utxo::Halving<InitialSubsidy, HalvingInterval>;
+	type CoinbaseMaturity = CoinbaseMaturity;
+	type MinFeeRate = MinFeeRate;
 }
 
 construct_runtime!(
@@ -245,7 +297,7 @@ construct_runtime!(
 		Balances: balances::{Module, Call, Storage, Config<T>, Event<T>},
 		TransactionPayment: transaction_payment::{Module, Storage},
 		Sudo: sudo::{Module, Call, Config<T>, Storage, Event<T>},
-		Utxo: utxo::{Module, Call, Config, Storage, Event},
+		Utxo: utxo::{Module, Call, Config, Storage, Event, ValidateUnsigned},
 	}
 );
 
@@ -360,21 +412,81 @@ impl_runtime_apis! {
 			// source: TransactionSource,
 			tx: <Block as BlockT>::Extrinsic,
 		) -> TransactionValidity {
-			// Extrinsics representing UTXO transaction need some special handling
-			if let Some(&utxo::Call::spend(ref transaction)) = IsSubType::<Utxo, Runtime>::is_sub_type(&tx.function) {
-				match Utxo::validate_transaction(&transaction) {
-					// Transaction verification failed
-					Err(e) => {
-						sp_runtime::print(e);
-						return Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(1)));
-					}
-					// Race condition, or Transaction is good to go
-					Ok(tv) => { return Ok(tv); }
-				}
+			// Unsigned UTXO spends are validated by `utxo::Module`'s `ValidateUnsigned`
+			Executive::validate_transaction(tx)
+		}
+	}
+
+	impl utxo::UtxoApi<Block> for Runtime {
+		fn utxo(outpoint: Hash) -> Option<utxo::TransactionOutput> {
+			Utxo::utxo(&outpoint)
+		}
+
+		fn outpoints_of(pubkey: Hash) -> Vec<Hash> {
+			Utxo::outpoints_of(&pubkey)
+		}
+
+		fn balance_of(pubkey: Hash) -> utxo::Value {
+			Utxo::balance_of(&pubkey)
+		}
+
+		fn dry_run(transaction: utxo::Transaction) -> Result<ValidTransaction, Vec<u8>> {
+			Utxo::validate_transaction(&transaction).map_err(|e| e.as_str().as_bytes().to_vec())
+		}
+
+		fn compute_outpoints(transaction: utxo::Transaction) -> Vec<Hash> {
+			Utxo::compute_outpoints(&transaction)
+		}
+
+		fn txid(transaction: utxo::Transaction) -> Hash {
+			Utxo::txid(&transaction)
+		}
+
+		fn witness_hash(transaction: utxo::Transaction) -> Hash {
+			Utxo::witness_hash(&transaction)
+		}
+
+		fn transaction_location(txid: Hash) -> Option<utxo::TransactionLocation> {
+			Utxo::transaction_location(txid)
+		}
+
+		fn total_supply() -> utxo::Value {
+			Utxo::total_supply()
+		}
+
+		fn can_replace(original: utxo::Transaction, replacement: utxo::Transaction) -> bool {
+			Utxo::can_replace(&original, &replacement)
+		}
+
+		fn signing_payload(transaction: utxo::Transaction, index: u32) -> Option<Vec<u8>> {
+			if !Utxo::genesis_hash_known() {
+				return None;
 			}
+			Utxo::signing_payload(&transaction, index as usize)
+		}
 
-			// Fall back to default logic for non UTXO-spending extrinsics
-			Executive::validate_transaction(tx)
+		fn signing_prefix() -> Option<Vec<u8>> {
+			if !Utxo::genesis_hash_known() {
+				return None;
+			}
+			Some(Utxo::signing_prefix())
+		}
+	}
+
+	#[cfg(feature = "runtime-benchmarks")]
+	impl frame_benchmarking::Benchmark<Block> for Runtime {
+		fn dispatch_benchmark(
+			module: Vec<u8>,
+			extrinsic: Vec<u8>,
+			steps: Vec<u32>,
+			repeat: u32,
+		) -> Option<Vec<frame_benchmarking::BenchmarkResults>> {
+			use frame_benchmarking::Benchmarking;
+
+			match module.as_slice() {
+				b"utxo" => Utxo::run_benchmark(extrinsic, steps, repeat).ok(),
+				_ => None,
+			}
 		}
 	}
 }
@@ -0,0 +1,498 @@
+//! Spending conditions for `Lock::ScriptHash` outputs.
+//!
+//! A script is a small predicate tree that is revealed by the spender and
+//! evaluated against the input's witness stack: signatures, hash preimages
+//! and branch choices, consumed front to back. Evaluation is deterministic,
+//! never backtracks and charges weight for every node it visits.
+//!
+//! Keys are account ids in the sense of `MultiSigner::into_account`: the
+//! sr25519 or ed25519 public key itself, or the BlakeTwo256 hash of a
+//! compressed ECDSA public key. A signature witness item is a SCALE encoded
+//! `MultiSignature`, which names the scheme, or a raw 64-byte sr25519 signature.
+
+use codec::{Decode, Encode, Input};
+use frame_support::weights::Weight;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_core::{H256, H512};
+use sp_core::sr25519::Signature;
+use sp_runtime::{AccountId32, MultiSignature, traits::Verify};
+use sp_std::prelude::*;
+use crate::utxo::BlockNumber;
+
+/// Weight of visiting a node that does no cryptographic work
+pub const OPCODE_WEIGHT: Weight = 100;
+/// Weight of verifying one signature of any scheme
+pub const SIGNATURE_WEIGHT: Weight = 10_000;
+/// Weight of hashing one preimage, on top of `HASH_BYTE_WEIGHT` per byte
+pub const HASH_WEIGHT: Weight = 1_000;
+/// Weight of hashing one byte of a preimage
+pub const HASH_BYTE_WEIGHT: Weight = 1;
+/// Most weight a single input's script may consume
+pub const MAX_SCRIPT_WEIGHT: Weight = 1_000_000;
+/// Deepest nesting of `And` / `Or` nodes that is decoded or evaluated
+pub const MAX_SCRIPT_DEPTH: u32 = 16;
+
+/// Spending condition.
+///
+/// Scripts arrive in unauthenticated extrinsics, so decoding fails for scripts
+/// nested deeper than `MAX_SCRIPT_DEPTH`, before any of it is hashed, cloned
+/// or evaluated.
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Hash, Debug)]
+pub enum Script {
+	/// Consumes one signature that must belong to this key
+	Key(H256),
+	/// Consumes one item per key, in the order of the keys: a signature of
+	/// that key, or nothing for keys that do not sign. Exactly `threshold`
+	/// keys have to sign
+	MultiSig {
+		threshold: u32,
+		pubkeys: Vec<H256>,
+	},
+	/// Consumes a preimage of this BlakeTwo256 hash
+	Blake2Preimage(H256),
+	/// Consumes a preimage of this SHA-256 hash
+	Sha256Preimage(H256),
+	/// Satisfied from this block height on
+	After(BlockNumber),
+	/// Satisfied if the spending input's `sequence` is at least this many blocks
+	Older(BlockNumber),
+	/// Satisfied if both conditions are
+	And(Box<Script>, Box<Script>),
+	/// Consumes a branch choice, `[0]` for the left and `[1]` for the right
+	/// condition, and is satisfied if the chosen condition is
+	Or(Box<Script>, Box<Script>),
+}
+
+impl Script {
+	/// Whether no node is nested more than `depth` deep below this one.
+	/// Stops looking at that depth, however deep the script is.
+	pub fn fits_depth(&self, depth: u32) -> bool {
+		match self {
+			Script::And(left, right) | Script::Or(left, right) => {
+				depth > 0 && left.fits_depth(depth - 1) && right.fits_depth(depth - 1)
+			}
+			_ => true,
+		}
+	}
+
+	/// Number of nodes of this script, every one of which evaluation visits at most once
+	pub fn nodes(&self) -> u32 {
+		match self {
+			Script::And(left, right) | Script::Or(left, right) => {
+				left.nodes().saturating_add(right.nodes()).saturating_add(1)
+			}
+			_ => 1,
+		}
+	}
+
+	/// Decode a script whose root is nested `depth` deep
+	fn decode_nested<I: Input>(input: &mut I, depth: u32) -> Result<Self, codec::Error> {
+		if depth > MAX_SCRIPT_DEPTH {
+			return Err("Script is nested too deeply".into());
+		}
+
+		// variant indices follow the declaration order, as in the derived `Encode`
+		match input.read_byte()? {
+			0 => Ok(Script::Key(Decode::decode(input)?)),
+			1 => Ok(Script::MultiSig {
+				threshold: Decode::decode(input)?,
+				pubkeys: Decode::decode(input)?,
+			}),
+			2 => Ok(Script::Blake2Preimage(Decode::decode(input)?)),
+			3 => Ok(Script::Sha256Preimage(Decode::decode(input)?)),
+			4 => Ok(Script::After(Decode::decode(input)?)),
+			5 => Ok(Script::Older(Decode::decode(input)?)),
+			6 => Ok(Script::And(
+				Box::new(Self::decode_nested(input, depth + 1)?),
+				Box::new(Self::decode_nested(input, depth + 1)?),
+			)),
+			7 => Ok(Script::Or(
+				Box::new(Self::decode_nested(input, depth + 1)?),
+				Box::new(Self::decode_nested(input, depth + 1)?),
+			)),
+			_ => Err("No such variant in enum Script".into()),
+		}
+	}
+}
+
+impl Decode for Script {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+		Self::decode_nested(input, 0)
+	}
+}
+
+/// Reasons a script is not satisfied
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum ScriptError {
+	/// A signature does not match its key
+	BadSignature,
+	/// A preimage does not match its hash
+	BadPreimage,
+	/// A branch choice is neither `[0]` nor `[1]`
+	BadBranch,
+	/// The spending input's `sequence` is too low
+	SequenceTooLow,
+	/// The witness ran out of items
+	WitnessTooShort,
+	/// Witness items were left after evaluation
+	WitnessTooLong,
+	/// Evaluation would exceed `MAX_SCRIPT_WEIGHT`
+	TooExpensive,
+	/// `And` / `Or` nodes are nested deeper than `MAX_SCRIPT_DEPTH`
+	TooDeep,
+}
+
+/// Data of the spending input a script is evaluated against
+pub struct Context<'a> {
+	/// Bytes the signatures have to be made over
+	pub payload: &'a [u8],
+	/// `sequence` of the spending input
+	pub sequence: BlockNumber,
+}
+
+/// Outcome of a successful evaluation
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct Evaluation {
+	/// Weight consumed by the evaluation
+	pub weight: Weight,
+	/// Height from which the `After` conditions on the evaluated path hold
+	pub unlock_at: Option<BlockNumber>,
+}
+
+/// Evaluate `script` against `witness`, which has to be consumed completely.
+pub fn evaluate(script: &Script, witness: &[Vec<u8>], context: &Context) -> Result<Evaluation, ScriptError> {
+	let mut interpreter = Interpreter {
+		witness: witness.iter(),
+		context,
+		evaluation: Evaluation::default(),
+	};
+
+	interpreter.eval(script, 0)?;
+	if interpreter.witness.next().is_some() {
+		return Err(ScriptError::WitnessTooLong);
+	}
+	Ok(interpreter.evaluation)
+}
+
+/// Most weight evaluating any script of at most `nodes` nodes against `witness`
+/// can consume. Every node is visited and every witness item is consumed at
+/// most once, as a signature, a preimage or a branch choice. Bounded by
+/// `MAX_SCRIPT_WEIGHT`, at which evaluation is aborted.
+pub fn max_weight(nodes: u32, witness: &[Vec<u8>]) -> Weight {
+	let items = witness.iter().fold(0 as Weight, |weight, item| {
+		let preimage = HASH_WEIGHT.saturating_add(HASH_BYTE_WEIGHT.saturating_mul(item.len() as Weight));
+		weight.saturating_add(SIGNATURE_WEIGHT.max(preimage))
+	});
+	OPCODE_WEIGHT.saturating_mul(nodes as Weight).saturating_add(items).min(MAX_SCRIPT_WEIGHT)
+}
+
+/// Decode a signature witness item, see the module documentation.
+pub fn decode_signature(item: &[u8]) -> Option<MultiSignature> {
+	if item.len() == 64 {
+		let signature = Signature::from_raw(*H512::from_slice(item).as_fixed_bytes());
+		return Some(MultiSignature::Sr25519(signature));
+	}
+	let mut input = item;
+	let signature = MultiSignature::decode(&mut input).ok()?;
+	if !input.is_empty() {
+		return None;
+	}
+	Some(signature)
+}
+
+struct Interpreter<'a, 'b> {
+	witness: sp_std::slice::Iter<'a, Vec<u8>>,
+	context: &'b Context<'b>,
+	evaluation: Evaluation,
+}
+
+impl<'a, 'b> Interpreter<'a, 'b> {
+	fn charge(&mut self, weight: Weight) -> Result<(), ScriptError> {
+		self.evaluation.weight = self.evaluation.weight.saturating_add(weight);
+		if self.evaluation.weight > MAX_SCRIPT_WEIGHT {
+			return Err(ScriptError::TooExpensive);
+		}
+		Ok(())
+	}
+
+	fn next_item(&mut self) -> Result<&'a [u8], ScriptError> {
+		self.witness.next().map(|item| &item[..]).ok_or(ScriptError::WitnessTooShort)
+	}
+
+	fn verify(&mut self, signature: &[u8], pubkey: &H256) -> Result<(), ScriptError> {
+		self.charge(SIGNATURE_WEIGHT)?;
+		let signature = decode_signature(signature).ok_or(ScriptError::BadSignature)?;
+		if !signature.verify(self.context.payload, &AccountId32::from(pubkey.0)) {
+			return Err(ScriptError::BadSignature);
+		}
+		Ok(())
+	}
+
+	fn preimage(&mut self) -> Result<&'a [u8], ScriptError> {
+		let preimage = self.next_item()?;
+		self.charge(HASH_WEIGHT.saturating_add(HASH_BYTE_WEIGHT.saturating_mul(preimage.len() as Weight)))?;
+		Ok(preimage)
+	}
+
+	fn eval(&mut self, script: &Script, depth: u32) -> Result<(), ScriptError> {
+		if depth > MAX_SCRIPT_DEPTH {
+			return Err(ScriptError::TooDeep);
+		}
+
+		match script {
+			Script::Key(pubkey) => {
+				let signature = self.next_item()?;
+				self.verify(signature, pubkey)?;
+			}
+			Script::MultiSig { threshold, pubkeys } => {
+				// the items pair up with the keys, so every signature is checked against one key only
+				self.charge(OPCODE_WEIGHT)?;
+				let mut signed: u32 = 0;
+				for pubkey in pubkeys {
+					let signature = self.next_item()?;
+					if !signature.is_empty() {
+						self.verify(signature, pubkey)?;
+						signed = signed.saturating_add(1);
+					}
+				}
+				if signed != *threshold {
+					return Err(ScriptError::BadSignature);
+				}
+			}
+			Script::Blake2Preimage(hash) => {
+				let preimage = self.preimage()?;
+				if sp_io::hashing::blake2_256(preimage) != hash.0 {
+					return Err(ScriptError::BadPreimage);
+				}
+			}
+			Script::Sha256Preimage(hash) => {
+				let preimage = self.preimage()?;
+				if sp_io::hashing::sha2_256(preimage) != hash.0 {
+					return Err(ScriptError::BadPreimage);
+				}
+			}
+			Script::After(height) => {
+				self.charge(OPCODE_WEIGHT)?;
+				self.evaluation.unlock_at = self.evaluation.unlock_at.max(Some(*height));
+			}
+			Script::Older(blocks) => {
+				self.charge(OPCODE_WEIGHT)?;
+				if self.context.sequence < *blocks {
+					return Err(ScriptError::SequenceTooLow);
+				}
+			}
+			Script::And(left, right) => {
+				self.charge(OPCODE_WEIGHT)?;
+				self.eval(left, depth + 1)?;
+				self.eval(right, depth + 1)?;
+			}
+			Script::Or(left, right) => {
+				self.charge(OPCODE_WEIGHT)?;
+				match self.next_item()? {
+					[0] => self.eval(left, depth + 1)?,
+					[1] => self.eval(right, depth + 1)?,
+					_ => return Err(ScriptError::BadBranch),
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Tests for this module
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{Pair, ecdsa, ed25519, sr25519};
+	use sp_runtime::{MultiSigner, traits::IdentifyAccount};
+
+	const PAYLOAD: &[u8] = b"transaction";
+
+	fn key(seed: &str) -> (sr25519::Pair, H256) {
+		let pair = sr25519::Pair::from_string(&format!("//{}", seed), None).unwrap();
+		let pubkey = H256::from(pair.public());
+		(pair, pubkey)
+	}
+
+	fn sign(pair: &sr25519::Pair) -> Vec<u8> {
+		pair.sign(PAYLOAD).0.to_vec()
+	}
+
+	fn account(signer: impl Into<MultiSigner>) -> H256 {
+		let account: AccountId32 = signer.into().into_account();
+		H256::from_slice(account.as_ref())
+	}
+
+	fn context() -> Context<'static> {
+		Context { payload: PAYLOAD, sequence: 0 }
+	}
+
+	#[test]
+	fn key_and_multisig() {
+		let (alice, alice_key) = key("Alice");
+		let (bob, bob_key) = key("Bob");
+		let (charlie, charlie_key) = key("Charlie");
+
+		assert!(evaluate(&Script::Key(alice_key), &[sign(&alice)], &context()).is_ok());
+		assert_eq!(evaluate(&Script::Key(alice_key), &[sign(&bob)], &context()), Err(ScriptError::BadSignature));
+		assert_eq!(evaluate(&Script::Key(alice_key), &[], &context()), Err(ScriptError::WitnessTooShort));
+		assert_eq!(
+			evaluate(&Script::Key(alice_key), &[sign(&alice), sign(&alice)], &context()),
+			Err(ScriptError::WitnessTooLong)
+		);
+
+		let multisig = Script::MultiSig { threshold: 2, pubkeys: vec![alice_key, bob_key, charlie_key] };
+		let evaluation = evaluate(&multisig, &[sign(&alice), vec![], sign(&charlie)], &context()).unwrap();
+		assert_eq!(evaluation.weight, OPCODE_WEIGHT + 2 * SIGNATURE_WEIGHT);
+		assert_eq!(
+			evaluate(&multisig, &[sign(&charlie), vec![], sign(&alice)], &context()),
+			Err(ScriptError::BadSignature)
+		);
+		// exactly `threshold` keys sign, one item per key
+		assert_eq!(
+			evaluate(&multisig, &[sign(&alice), vec![], vec![]], &context()),
+			Err(ScriptError::BadSignature)
+		);
+		assert_eq!(
+			evaluate(&multisig, &[sign(&alice), sign(&charlie)], &context()),
+			Err(ScriptError::WitnessTooShort)
+		);
+	}
+
+	#[test]
+	fn signature_schemes() {
+		let sr25519 = sr25519::Pair::from_string("//Alice", None).unwrap();
+		let ed25519 = ed25519::Pair::from_string("//Alice", None).unwrap();
+		let ecdsa = ecdsa::Pair::from_string("//Alice", None).unwrap();
+		let sr25519_key = account(sr25519.public());
+		let ed25519_key = account(ed25519.public());
+		let ecdsa_key = account(ecdsa.public());
+
+		let sr25519_signature = MultiSignature::from(sr25519.sign(PAYLOAD)).encode();
+		let ed25519_signature = MultiSignature::from(ed25519.sign(PAYLOAD)).encode();
+		let ecdsa_signature = MultiSignature::from(ecdsa.sign(PAYLOAD)).encode();
+
+		assert!(evaluate(&Script::Key(sr25519_key), &[sr25519_signature.clone()], &context()).is_ok());
+		assert!(evaluate(&Script::Key(ed25519_key), &[ed25519_signature.clone()], &context()).is_ok());
+		assert!(evaluate(&Script::Key(ecdsa_key), &[ecdsa_signature.clone()], &context()).is_ok());
+
+		// signatures of one scheme never verify against keys of another
+		assert_eq!(
+			evaluate(&Script::Key(sr25519_key), &[ed25519_signature.clone()], &context()),
+			Err(ScriptError::BadSignature)
+		);
+		assert_eq!(
+			evaluate(&Script::Key(ed25519_key), &[ecdsa_signature.clone()], &context()),
+			Err(ScriptError::BadSignature)
+		);
+
+		// trailing bytes after an encoded signature are rejected
+		let mut padded = ed25519_signature.clone();
+		padded.push(0);
+		assert_eq!(evaluate(&Script::Key(ed25519_key), &[padded], &context()), Err(ScriptError::BadSignature));
+
+		let multisig = Script::MultiSig { threshold: 2, pubkeys: vec![sr25519_key, ed25519_key, ecdsa_key] };
+		assert!(evaluate(&multisig, &[sr25519_signature, vec![], ecdsa_signature], &context()).is_ok());
+	}
+
+	#[test]
+	fn preimages_and_time_locks() {
+		let (alice, alice_key) = key("Alice");
+		let secret = b"secret".to_vec();
+		let blake2 = H256::from(sp_io::hashing::blake2_256(&secret));
+		let sha256 = H256::from(sp_io::hashing::sha2_256(&secret));
+
+		assert!(evaluate(&Script::Blake2Preimage(blake2), &[secret.clone()], &context()).is_ok());
+		assert!(evaluate(&Script::Sha256Preimage(sha256), &[secret.clone()], &context()).is_ok());
+		assert_eq!(
+			evaluate(&Script::Sha256Preimage(blake2), &[secret.clone()], &context()),
+			Err(ScriptError::BadPreimage)
+		);
+
+		let vesting = Script::And(Box::new(Script::Key(alice_key)), Box::new(Script::After(10)));
+		assert_eq!(evaluate(&vesting, &[sign(&alice)], &context()).unwrap().unlock_at, Some(10));
+
+		let older = Script::Older(5);
+		assert_eq!(evaluate(&older, &[], &context()), Err(ScriptError::SequenceTooLow));
+		assert!(evaluate(&older, &[], &Context { payload: PAYLOAD, sequence: 5 }).is_ok());
+	}
+
+	#[test]
+	fn branches_and_limits() {
+		let (alice, alice_key) = key("Alice");
+		let (bob, bob_key) = key("Bob");
+		let either = Script::Or(Box::new(Script::Key(alice_key)), Box::new(Script::Key(bob_key)));
+
+		assert!(evaluate(&either, &[vec![0], sign(&alice)], &context()).is_ok());
+		assert!(evaluate(&either, &[vec![1], sign(&bob)], &context()).is_ok());
+		assert_eq!(evaluate(&either, &[vec![0], sign(&bob)], &context()), Err(ScriptError::BadSignature));
+		assert_eq!(evaluate(&either, &[vec![2], sign(&bob)], &context()), Err(ScriptError::BadBranch));
+
+		let deep = (0..=MAX_SCRIPT_DEPTH).fold(Script::After(0), |script, _| {
+			Script::And(Box::new(script), Box::new(Script::After(0)))
+		});
+		assert_eq!(evaluate(&deep, &[], &context()), Err(ScriptError::TooDeep));
+
+		let expensive = Script::MultiSig { threshold: 200, pubkeys: vec![bob_key; 200] };
+		assert_eq!(evaluate(&expensive, &vec![sign(&bob); 200], &context()), Err(ScriptError::TooExpensive));
+	}
+
+	#[test]
+	fn max_weight_bounds_evaluation() {
+		let (alice, alice_key) = key("Alice");
+		let (bob, bob_key) = key("Bob");
+		let (_, charlie_key) = key("Charlie");
+
+		// keys that do not sign still take a witness item
+		let partial = Script::MultiSig { threshold: 1, pubkeys: vec![charlie_key, bob_key, alice_key] };
+		let witness = [vec![], vec![], sign(&alice)];
+		let evaluation = evaluate(&partial, &witness, &context()).unwrap();
+		assert_eq!(evaluation.weight, OPCODE_WEIGHT + SIGNATURE_WEIGHT);
+		assert_eq!(max_weight(partial.nodes(), &witness), OPCODE_WEIGHT + 3 * SIGNATURE_WEIGHT);
+
+		let secret = b"secret".to_vec();
+		let hash = H256::from(sp_io::hashing::sha2_256(&secret));
+		let htlc = Script::Or(
+			Box::new(Script::And(Box::new(Script::Sha256Preimage(hash)), Box::new(Script::Key(bob_key)))),
+			Box::new(Script::And(Box::new(Script::Key(alice_key)), Box::new(Script::After(0)))),
+		);
+		assert_eq!(htlc.nodes(), 7);
+		for witness in vec![vec![vec![0], secret, sign(&bob)], vec![vec![1], sign(&alice)]] {
+			let evaluation = evaluate(&htlc, &witness, &context()).unwrap();
+			assert!(evaluation.weight <= max_weight(htlc.nodes(), &witness));
+		}
+
+		assert_eq!(max_weight(1, &vec![sign(&bob); 200]), MAX_SCRIPT_WEIGHT);
+	}
+
+	#[test]
+	fn decoding_is_depth_bounded() {
+		let (_, alice_key) = key("Alice");
+		let nest = |levels: u32| (0..levels).fold(Script::After(0), |script, _| {
+			Script::Or(Box::new(Script::Older(0)), Box::new(script))
+		});
+
+		// the deepest nodes of this script sit exactly at the limit
+		let deepest = Script::Or(Box::new(Script::Key(alice_key)), Box::new(nest(MAX_SCRIPT_DEPTH - 1)));
+		assert!(deepest.fits_depth(MAX_SCRIPT_DEPTH));
+		assert_eq!(Script::decode(&mut &deepest.encode()[..]).ok(), Some(deepest));
+
+		// one level more in the branch the spender would not pick fails to decode
+		let too_deep = Script::Or(Box::new(Script::Key(alice_key)), Box::new(nest(MAX_SCRIPT_DEPTH)));
+		assert!(!too_deep.fits_depth(MAX_SCRIPT_DEPTH));
+		assert!(Script::decode(&mut &too_deep.encode()[..]).is_err());
+
+		// every variant round trips
+		let all = Script::And(
+			Box::new(Script::MultiSig { threshold: 1, pubkeys: vec![alice_key] }),
+			Box::new(Script::And(
+				Box::new(Script::Blake2Preimage(alice_key)),
+				Box::new(Script::Sha256Preimage(alice_key)),
+			)),
+		);
+		assert_eq!(Script::decode(&mut &all.encode()[..]).ok(), Some(all));
+		assert!(Script::decode(&mut &[8u8][..]).is_err());
+	}
+}
@@ -1,20 +1,160 @@
-use super::Aura;
 use codec::{Decode, Encode};
 use frame_support::{
-	decl_event, decl_module, decl_storage,
+	decl_error, decl_event, decl_module, decl_storage,
 	dispatch::{DispatchResult, Vec},
 	ensure,
+	storage::IterableStorageMap,
+	traits::Get,
+	weights::{ClassifyDispatch, DispatchClass, PaysFee, WeighData, Weight},
 };
-use sp_core::{H256, H512};
+use sp_core::H256;
 #[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
-use sp_core::sr25519::{Public, Signature};
-use sp_runtime::traits::{BlakeTwo256, Hash, SaturatedConversion};
-use sp_std::collections::btree_map::BTreeMap;
-use sp_runtime::transaction_validity::{TransactionLongevity, ValidTransaction};
+use sp_runtime::Percent;
+use sp_runtime::traits::{BlakeTwo256, Hash, SaturatedConversion, Zero};
+use sp_std::{prelude::*, vec, borrow::Cow, collections::btree_map::BTreeMap, marker::PhantomData};
+use sp_runtime::transaction_validity::{
+	InvalidTransaction, TransactionLongevity, TransactionValidity, ValidTransaction,
+};
+use sp_runtime::traits::ValidateUnsigned;
+use system::ensure_none;
+use crate::script::{self, Script, ScriptError};
+
+/// Prefix of pool tags marking the spend of an outpoint. Transactions
+/// spending the same outpoint provide the same tag, so the pool only keeps the
+/// one with the higher priority, which lets unconfirmed spends be replaced by fee.
+pub const SPEND_TAG: &[u8] = b"utxo/spend";
+/// Domain separation tag starting every signing payload
+pub const SIGNING_TAG: &[u8] = b"utxo/sign";
+/// Outpoints tried for a reward output before its value is carried over to the next block
+pub const REWARD_OUTPOINT_ATTEMPTS: u32 = 16;
+
+/// Most inputs a transaction may have
+pub const MAX_INPUTS: usize = 1_000;
+/// Most outputs a transaction may have
+pub const MAX_OUTPUTS: usize = 1_000;
+/// Weight of a `spend` regardless of its size. The weights below are to be
+/// fit to the `spend` benchmark in `benchmarking.rs`.
+pub const SPEND_BASE_WEIGHT: Weight = 100_000;
+/// Weight of reading and removing the output an input spends
+pub const INPUT_WEIGHT: Weight = 50_000;
+/// Weight of storing and indexing an output
+pub const OUTPUT_WEIGHT: Weight = 50_000;
+/// Weight of decoding and hashing one byte of the encoded transaction
+pub const BYTE_WEIGHT: Weight = 10;
+/// Amount of weight fee rates are expressed per
+pub const FEE_RATE_WEIGHT: Weight = 1_000_000;
+/// Most nodes of the script of any lock but `Lock::ScriptHash`, those of `Lock::Htlc`
+pub const LOCK_SCRIPT_NODES: u32 = 7;
 
 pub trait Trait: system::Trait {
 	type Event: From<Event> + Into<<Self as system::Trait>::Event>;
+
+	/// Number of blocks a transaction stays in the transaction index after
+	/// its inclusion. `0` disables the index.
+	type TxIndexDepth: Get<BlockNumber>;
+
+	/// Authorities the transaction fees may be dispersed to
+	type Authorities: AuthoritiesProvider;
+
+	/// Author of the block being executed
+	type BlockAuthor: BlockAuthor;
+
+	/// How the transaction fees are split at the end of each block
+	type RewardPolicy: RewardPolicy;
+
+	/// Value newly minted each block and dispersed along with the fees
+	type Subsidy: SubsidySchedule;
+
+	/// Number of blocks reward outputs stay unspendable after their creation,
+	/// so a reorg before finality cannot erase rewards that were already spent
+	type CoinbaseMaturity: Get<BlockNumber>;
+
+	/// Least fee per `FEE_RATE_WEIGHT` of weight a transaction has to pay
+	type MinFeeRate: Get<Value>;
+}
+
+/// Issuance of new value per block
+pub trait SubsidySchedule {
+	/// Value minted in `block`
+	fn subsidy(block: BlockNumber) -> Value;
+}
+
+/// No issuance: the supply is fixed at genesis
+impl SubsidySchedule for () {
+	fn subsidy(_block: BlockNumber) -> Value {
+		0
+	}
+}
+
+/// `Initial` value per block, halved every `Interval` blocks. An `Interval`
+/// of `0` never halves.
+pub struct Halving<Initial, Interval>(PhantomData<(Initial, Interval)>);
+
+impl<Initial: Get<Value>, Interval: Get<BlockNumber>> SubsidySchedule for Halving<Initial, Interval> {
+	fn subsidy(block: BlockNumber) -> Value {
+		let halvings = block.checked_div(Interval::get()).unwrap_or(0);
+		if halvings >= Value::max_value().count_ones() as BlockNumber {
+			0
+		} else {
+			Initial::get() >> halvings
+		}
+	}
+}
+
+/// Source of the keys of the current block authoring authorities
+pub trait AuthoritiesProvider {
+	/// Keys of the current authorities, in the `Lock::Key` format
+	fn authorities() -> Vec<H256>;
+}
+
+/// Source of the key of the author of the block being executed
+pub trait BlockAuthor {
+	/// Key of the block author in the `Lock::Key` format, if known
+	fn block_author() -> Option<H256>;
+}
+
+/// Split of the fees collected in a block
+pub trait RewardPolicy {
+	/// Shares of `reward` for the block `author` and the `authorities`.
+	/// Shares must not add up to more than `reward`; the rest is carried over
+	/// to the next block.
+	fn split(reward: Value, author: Option<&H256>, authorities: &[H256]) -> Vec<(H256, Value)>;
+}
+
+/// The block author receives the whole reward
+pub struct AuthorTakesAll;
+
+impl RewardPolicy for AuthorTakesAll {
+	fn split(reward: Value, author: Option<&H256>, _authorities: &[H256]) -> Vec<(H256, Value)> {
+		author.map(|author| vec![(*author, reward)]).unwrap_or_default()
+	}
+}
+
+/// Every authority receives an equal share, whoever authored the block
+pub struct EvenSplit;
+
+impl RewardPolicy for EvenSplit {
+	fn split(reward: Value, _author: Option<&H256>, authorities: &[H256]) -> Vec<(H256, Value)> {
+		match reward.checked_div(authorities.len() as Value) {
+			Some(share) => authorities.iter().map(|authority| (*authority, share)).collect(),
+			None => Vec::new(),
+		}
+	}
+}
+
+/// The `Treasury` key receives `Share` of the reward and the block author the rest
+pub struct AuthorAndTreasury<Treasury, Share>(PhantomData<(Treasury, Share)>);
+
+impl<Treasury: Get<H256>, Share: Get<Percent>> RewardPolicy for AuthorAndTreasury<Treasury, Share> {
+	fn split(reward: Value, author: Option<&H256>, _authorities: &[H256]) -> Vec<(H256, Value)> {
+		let treasury_share = Share::get() * reward;
+		let mut shares = vec![(Treasury::get(), treasury_share)];
+		if let Some(author) = author {
+			shares.push((*author, reward.saturating_sub(treasury_share)));
+		}
+		shares
+	}
 }
 
 /// Single transaction input that refers to one UTXO
@@ -24,21 +164,221 @@ pub struct TransactionInput {
 	/// Reference to an UTXO to be spent
 	pub outpoint: H256,
 	/// Proof that transaction owner is authorized to spend referred UTXO &
-	/// that the entire transaction is untampered
-	pub sigscript: H512, 
+	/// that the entire transaction is untampered: the witness items the
+	/// spending `Script` consumes, such as signatures and hash preimages
+	pub sigscript: Vec<Vec<u8>>,
+	/// Spending condition of a `Lock::ScriptHash` output, revealed by the spender
+	pub script: Option<Script>,
+	/// Relative time lock: the input is only valid once this many blocks
+	/// have passed since the referred UTXO was created
+	pub sequence: BlockNumber,
+	/// Parts of the transaction the signatures of this input commit to
+	pub sighash: SigHash,
+}
+
+/// Parts of a transaction an input's signatures commit to, besides the input itself
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Encode, Decode, Hash, Debug)]
+pub enum SigHash {
+	/// All inputs and all outputs
+	All,
+	/// All inputs and the output at the index of the input
+	Single,
+	/// All outputs, so anyone can add inputs after signing
+	AllAnyoneCanPay,
+	/// The output at the index of the input, so anyone can add inputs and
+	/// outputs after signing
+	SingleAnyoneCanPay,
+}
+
+impl Default for SigHash {
+	fn default() -> Self {
+		SigHash::All
+	}
 }
 
 pub type Value = u128;
+
+/// Spending condition of an output
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode, Hash, Debug)]
+pub enum Lock {
+	/// Spendable with a signature of this key. Keys are account ids as in
+	/// `MultiSigner::into_account`, so sr25519, ed25519 and ECDSA keys can all
+	/// own outputs; see the `script` module for the signature encoding.
+	Key(H256),
+	/// Spendable with signatures of any `threshold` of these keys. The witness
+	/// holds one item per key, in key order: a signature of that key, or an
+	/// empty item for the keys that do not sign.
+	MultiSig {
+		threshold: u32,
+		pubkeys: Vec<H256>,
+	},
+	/// Spendable by revealing a `Script` with this BlakeTwo256 hash and
+	/// satisfying it
+	ScriptHash(H256),
+	/// Hash-time-locked contract. Spendable by `recipient` revealing a preimage
+	/// of `hash`, with witness `[[0], preimage, recipient signature]`, or by
+	/// `sender` from block `timeout` on, with witness `[[1], sender signature]`
+	Htlc {
+		hash: HashLock,
+		recipient: H256,
+		sender: H256,
+		timeout: BlockNumber,
+	},
+}
+
+/// Hash the preimage revealed to claim a `Lock::Htlc` output has to match
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode, Hash, Debug)]
+pub enum HashLock {
+	/// BlakeTwo256 hash
+	Blake2(H256),
+	/// SHA-256 hash
+	Sha256(H256),
+}
+
+impl Default for Lock {
+	fn default() -> Self {
+		Lock::Key(H256::zero())
+	}
+}
+
+impl Lock {
+	/// Public keys involved in spending an output with this lock
+	pub fn owners(&self) -> Vec<H256> {
+		match self {
+			Lock::Key(pubkey) => vec![*pubkey],
+			Lock::MultiSig { pubkeys, .. } => pubkeys.clone(),
+			Lock::ScriptHash(_) => Vec::new(),
+			Lock::Htlc { recipient, sender, .. } => vec![*recipient, *sender],
+		}
+	}
+
+	/// Script an output with this lock is spent by, `None` for `Lock::ScriptHash`
+	/// whose script is revealed by the spending input
+	pub fn script(&self) -> Option<Script> {
+		match self {
+			Lock::Key(pubkey) => Some(Script::Key(*pubkey)),
+			Lock::MultiSig { threshold, pubkeys } => Some(Script::MultiSig {
+				threshold: *threshold,
+				pubkeys: pubkeys.clone(),
+			}),
+			Lock::ScriptHash(_) => None,
+			Lock::Htlc { hash, recipient, sender, timeout } => {
+				let preimage = match hash {
+					HashLock::Blake2(hash) => Script::Blake2Preimage(*hash),
+					HashLock::Sha256(hash) => Script::Sha256Preimage(*hash),
+				};
+				Some(Script::Or(
+					Box::new(Script::And(Box::new(preimage), Box::new(Script::Key(*recipient)))),
+					Box::new(Script::And(Box::new(Script::Key(*sender)), Box::new(Script::After(*timeout)))),
+				))
+			}
+		}
+	}
+
+	/// Whether `pubkey` alone can spend an output with this lock at `block`
+	pub fn spendable_by(&self, pubkey: &H256, block: BlockNumber) -> bool {
+		match self {
+			Lock::Key(key) => key == pubkey,
+			Lock::MultiSig { threshold, pubkeys } => *threshold == 1 && pubkeys.contains(pubkey),
+			Lock::ScriptHash(_) => false,
+			// the recipient needs the preimage as well
+			Lock::Htlc { sender, timeout, .. } => sender == pubkey && block >= *timeout,
+		}
+	}
+}
+
+/// Block height used by output time locks
+pub type BlockNumber = u64;
 /// Single transaction output to create upon transaction dispatch
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 #[derive(PartialEq, Eq, PartialOrd, Ord, Default, Clone, Encode, Decode, Hash, Debug)]
 pub struct TransactionOutput {
 	/// Value associated with this output
 	pub value: Value, 
-	/// Public key associated with this output. In order to spend this output
-	/// owner must provide a proof by hashing the whole `Transaction` and
-	/// signing it with a corresponding private key.
-	pub pubkey: H256, 
+	/// Public keys associated with this output. In order to spend this output
+	/// owners must provide a proof by hashing the whole `Transaction` and
+	/// signing it with the corresponding private keys.
+	pub lock: Lock,
+	/// Absolute time lock: if set, the output cannot be spent in blocks
+	/// below this height. See `TransactionInput::sequence` for relative locks.
+	pub lock_until: Option<BlockNumber>,
+}
+
+/// `TransactionOutput` as stored before outputs carried a `Lock`: a single
+/// sr25519 owner key, followed by `lock_until` once outputs had it
+struct LegacyOutput {
+	value: Value,
+	pubkey: H256,
+	lock_until: Option<BlockNumber>,
+}
+
+impl Decode for LegacyOutput {
+	fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+		let value = Value::decode(input)?;
+		let pubkey = H256::decode(input)?;
+		// outputs stored before `lock_until` end after the key
+		let lock_until = match input.remaining_len()? {
+			Some(0) => None,
+			_ => Option::<BlockNumber>::decode(input)?,
+		};
+		Ok(LegacyOutput { value, pubkey, lock_until })
+	}
+}
+
+/// Weight of a `spend` of `transaction`, growing with its inputs, the
+/// scripts they satisfy, its outputs and its encoded length
+pub fn spend_weight(transaction: &Transaction) -> Weight {
+	let inputs = transaction.inputs.iter().fold(0 as Weight, |weight, input| {
+		// scripts are evaluated by `pre_dispatch` and again by `spend` itself
+		let scripts = script_weight(input).saturating_mul(2);
+		weight.saturating_add(INPUT_WEIGHT.saturating_add(scripts))
+	});
+	let outputs = OUTPUT_WEIGHT.saturating_mul(transaction.outputs.len() as Weight);
+	let bytes = BYTE_WEIGHT.saturating_mul(transaction.encode().len() as Weight);
+
+	SPEND_BASE_WEIGHT.saturating_add(inputs).saturating_add(outputs).saturating_add(bytes)
+}
+
+/// Most weight evaluating the script `input` satisfies can consume, whichever
+/// output it spends. Weighed from the witness and the revealed script alone,
+/// since the spent output may not exist yet and reading it would not be charged.
+pub fn script_weight(input: &TransactionInput) -> Weight {
+	let nodes = input.script.as_ref().map_or(0, Script::nodes).max(LOCK_SCRIPT_NODES);
+	script::max_weight(nodes, &input.sigscript)
+}
+
+/// Dispatch weight of `spend`, see `spend_weight`
+pub struct SpendWeight;
+
+impl<'a> WeighData<(&'a Transaction,)> for SpendWeight {
+	fn weigh_data(&self, (transaction,): (&'a Transaction,)) -> Weight {
+		spend_weight(transaction)
+	}
+}
+
+impl<'a> ClassifyDispatch<(&'a Transaction,)> for SpendWeight {
+	fn classify_dispatch(&self, _: (&'a Transaction,)) -> DispatchClass {
+		DispatchClass::Normal
+	}
+}
+
+impl<'a> PaysFee<(&'a Transaction,)> for SpendWeight {
+	fn pays_fee(&self, _: (&'a Transaction,)) -> bool {
+		true
+	}
+}
+
+/// Position of an included transaction in the chain
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, Default, Debug)]
+pub struct TransactionLocation {
+	/// Height of the including block
+	pub block: BlockNumber,
+	/// Index of the `spend` extrinsic in the including block
+	pub extrinsic_index: u32,
 }
 
 /// Single transaction to be dispatched
@@ -61,11 +401,54 @@ decl_storage! {
 			.collect::<Vec<_>>()
 		}): map hasher(identity) H256 => Option<TransactionOutput>;
 
+		/// Whether `UtxoStore` holds outputs in the current `TransactionOutput` format.
+		/// Chains started before outputs carried a `Lock` translate them on runtime upgrade.
+		OutputsUpgraded build(|_| true): bool;
+
+		/// Outpoints of unspent outputs indexed by every public key of their lock.
+		/// The outpoint is stored as the value as well so that all outputs of
+		/// one owner can be listed with `iter_prefix`.
+		UtxoOwners get(fn utxo_owners) build(|config: &GenesisConfig| {
+			config.genesis_utxo
+			.iter()
+			.flat_map(|u| {
+				let outpoint = BlakeTwo256::hash_of(u);
+				u.lock.owners().into_iter().map(move |owner| (owner, outpoint, outpoint))
+			})
+			.collect::<Vec<_>>()
+		}): double_map hasher(blake2_128_concat) H256, hasher(identity) H256 => Option<H256>;
+
+		/// Block height each unspent output was created at.
+		/// Outputs created at genesis, or before heights were recorded, read as `0`.
+		pub UtxoCreated get(fn utxo_created): map hasher(identity) H256 => BlockNumber;
+
+		/// Whether `UtxoOwners` covers every output in `UtxoStore`.
+		/// Chains started before the index existed back-fill it on runtime upgrade.
+		OwnerIndexBuilt build(|_| true): bool;
 
 		/// Total reward value to be redistributed among authorities.
-		/// It is accumulated from transactions during block execution
+		/// It is accumulated from transaction fees and the block subsidy
 		/// and then dispersed to validators on block finalization.
 		pub RewardTotal get(fn reward_total): Value;
+
+		/// Location of the transactions included in the last `TxIndexDepth`
+		/// blocks, by transaction id
+		pub TransactionIndex get(fn transaction_location): map hasher(identity) H256 => Option<TransactionLocation>;
+
+		/// Ids of the transactions included at each height, for pruning `TransactionIndex`
+		TransactionsAt: map hasher(twox_64_concat) BlockNumber => Vec<H256>;
+
+		/// Value in existence: the unspent outputs plus the undistributed `RewardTotal`.
+		/// It grows by the block subsidy only, as fees move value without creating it.
+		pub TotalSupply get(fn total_supply) build(|config: &GenesisConfig| {
+			config.genesis_utxo
+			.iter()
+			.fold(0, |total: Value, u| total.saturating_add(u.value))
+		}): Value;
+
+		/// Whether `TotalSupply` is tracked.
+		/// Chains started before it was tracked compute it on runtime upgrade.
+		SupplyTracked build(|_| true): bool;
 	}
 
 	add_extra_genesis {
@@ -76,35 +459,57 @@ decl_storage! {
 // External functions: callable by the end user
 decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
 		fn deposit_event() = default;
 
+		/// Number of blocks a transaction stays in the transaction index
+		const TxIndexDepth: BlockNumber = T::TxIndexDepth::get();
+
+		/// Number of blocks reward outputs stay unspendable
+		const CoinbaseMaturity: BlockNumber = T::CoinbaseMaturity::get();
 
-		pub fn spend(_origin, transaction: Transaction) -> DispatchResult {
+		/// Least fee per `FEE_RATE_WEIGHT` of weight a transaction has to pay
+		const MinFeeRate: Value = T::MinFeeRate::get();
+
+		fn on_runtime_upgrade() {
+			// the indexes below read outputs, so they are translated first
+			Self::upgrade_outputs();
+			Self::build_owner_index();
+			Self::track_supply();
+		}
+
+		/// Spend UTXOs. Submitted unsigned: the transaction carries its own
+		/// authorization in the input witnesses and pays for itself with its fee.
+		#[weight = SpendWeight]
+		pub fn spend(origin, transaction: Transaction) -> DispatchResult {
+			ensure_none(origin)?;
 			// check the transaction is valid
 			let transaction_validity = Self::validate_transaction(&transaction)?;
+			// the pool may keep transactions with unmet requirements, dispatch must not
+			ensure!(transaction_validity.requires.is_empty(), Error::<T>::MissingInputs);
+			// collect HTLC preimages before the claimed outputs are removed
+			let preimages = Self::htlc_preimages(&transaction);
 			// write to storage
-			Self::update_storage(&transaction, transaction_validity.priority as Value)?;
+			Self::update_storage(&transaction, Self::fee(&transaction))?;
+			Self::index_transaction(&transaction);
 
 			// emit success event
+			for (outpoint, preimage) in preimages {
+				Self::deposit_event(Event::HtlcClaimed(outpoint, preimage));
+			}
 			Self::deposit_event(Event::TransactionSuccess(transaction));
 
 			Ok(())
 		}
 
 		/// Handler called by the system on block finalization
-		fn on_finalize() {
-			let auth: Vec<_> = Aura::authorities().iter().map(|x| {
-				let r: &Public = x.as_ref();
-				r.0.into()
-			}).collect();
+		fn on_finalize(n: T::BlockNumber) {
+			let n = n.saturated_into::<BlockNumber>();
+			Self::prune_transaction_index(n);
 
-			Self::disperse_reward(&auth);
-			// match T::BlockAuthor::block_author() {
-			// 	// Block author did not provide key to claim reward
-			// 	None => Self::deposit_event(Event::RewardsWasted),
-			// 	// Block author did provide key, so issue thir reward
-			// 	Some(author) => Self::disperse_reward(&author),
-			// }
+			Self::mint_subsidy(n);
+			Self::disperse_reward(T::BlockAuthor::block_author(), &T::Authorities::authorities());
 		}
 	}
 
@@ -114,68 +519,229 @@ decl_event! {
 	pub enum Event {
 		/// Transaction was executed successfully
 		TransactionSuccess(Transaction),
+		/// HTLC output was claimed by its recipient revealing this preimage
+		HtlcClaimed(H256, Vec<u8>),
+		/// Output was stored under this outpoint
+		UtxoCreated(H256, Lock, Value),
+		/// Output stored under this outpoint was spent
+		UtxoSpent(H256),
+		/// Transaction paid this fee into `RewardTotal`
+		FeePaid(Value),
+		/// Authority was rewarded with the output at this outpoint
+		RewardDistributed(H256, H256, Value),
+		/// Block subsidy of this value was minted
+		SubsidyMinted(Value),
+	}
+}
+
+decl_error! {
+	/// Reasons a UTXO transaction is rejected
+	pub enum Error for Module<T: Trait> {
+		/// Transaction has no inputs
+		EmptyInputs,
+		/// Transaction has no outputs
+		EmptyOutputs,
+		/// Transaction has more than `MAX_INPUTS` inputs
+		TooManyInputs,
+		/// Transaction has more than `MAX_OUTPUTS` outputs
+		TooManyOutputs,
+		/// Transaction weighs more than a block may hold
+		TooHeavy,
+		/// An input is used more than once
+		DuplicateInput,
+		/// An output is defined more than once
+		DuplicateOutput,
+		/// Some inputs refer to outputs that do not exist or are already spent
+		MissingInputs,
+		/// Some inputs refer to outputs that are still time locked
+		OutputLocked,
+		/// Input signatures do not satisfy the lock of the referred output
+		BadSignature,
+		/// An input signs the output at its own index, which does not exist
+		NoSignedOutput,
+		/// Revealed script is missing or does not match the script hash of the referred output
+		ScriptMismatch,
+		/// Input witness does not satisfy the spending script
+		ScriptFailed,
+		/// Spending script is too expensive or too deeply nested to evaluate
+		ScriptTooExpensive,
+		/// Sum of input or output values overflows
+		ValueOverflow,
+		/// An output has zero value
+		ZeroValueOutput,
+		/// A multisig output requires no signatures or more signatures than it has keys
+		InvalidLock,
+		/// Transaction has more outputs than can be indexed
+		OutputIndexOverflow,
+		/// An output would be stored under an outpoint that is already taken
+		OutputExists,
+		/// Total output value exceeds total input value
+		InsufficientInput,
+		/// Fee is below the minimum fee rate
+		FeeTooLow,
+		/// Fee computation underflows
+		RewardUnderflow,
+		/// Accumulated block reward overflows
+		RewardOverflow,
+		/// Spends are only accepted from block 1 on, see `genesis_hash_known`
+		GenesisHashUnknown,
 	}
 }
 
 
 impl<T: Trait> Module<T> {
-	// Strips a transaction of its Signature fields by replacing value with ZERO-initialized fixed hash.
-	pub fn get_simple_transaction(transaction: &Transaction) -> Vec<u8> {//&'a [u8] {
+	/// Prefix of every signing payload: `SIGNING_TAG`, the genesis hash and
+	/// the runtime `spec_version`, so signatures cannot be replayed on other
+	/// chains or after runtime upgrades.
+	///
+	/// Only valid once `genesis_hash_known`: spends can be signed from block 1 on.
+	pub fn signing_prefix() -> Vec<u8> {
+		let mut prefix = SIGNING_TAG.to_vec();
+		<system::Module<T>>::block_hash(T::BlockNumber::zero()).encode_to(&mut prefix);
+		T::Version::get().spec_version.encode_to(&mut prefix);
+		prefix
+	}
+
+	/// Whether the runtime knows the genesis hash the signing prefix commits to.
+	/// `system::BlockHash(0)` is only set when block 1 is initialized and holds
+	/// a placeholder, the same on every chain, in the genesis state.
+	pub fn genesis_hash_known() -> bool {
+		!<system::Module<T>>::block_number().is_zero()
+	}
+
+	/// Copy of `transaction` with every `sigscript` emptied, the part that
+	/// signatures and the transaction id cover
+	fn strip_witnesses(transaction: &Transaction) -> Transaction {
 		let mut trx = transaction.clone();
 		for input in trx.inputs.iter_mut() {
-			input.sigscript = H512::zero();
+			input.sigscript = Vec::new();
 		}
+		trx
+	}
+
+	/// Signing payload committing to all inputs and outputs (`SigHash::All`):
+	/// the signing prefix followed by the transaction without its witnesses
+	pub fn get_simple_transaction(transaction: &Transaction) -> Vec<u8> {
+		let mut payload = Self::signing_prefix();
+		Self::strip_witnesses(transaction).encode_to(&mut payload);
+		payload
+	}
 
-		trx.encode()
+	/// Bytes the signatures of input `index` of `transaction` are made over,
+	/// as selected by the input's `SigHash`. `None` if the input does not
+	/// exist or signs an output that does not exist.
+	pub fn signing_payload(transaction: &Transaction, index: usize) -> Option<Vec<u8>> {
+		let input = transaction.inputs.get(index)?;
+		let inputs = match input.sighash {
+			SigHash::All | SigHash::Single => transaction.inputs.clone(),
+			SigHash::AllAnyoneCanPay | SigHash::SingleAnyoneCanPay => vec![input.clone()],
+		};
+		match input.sighash {
+			SigHash::All | SigHash::AllAnyoneCanPay => {
+				let outputs = transaction.outputs.clone();
+				Some(Self::get_simple_transaction(&Transaction { inputs, outputs }))
+			}
+			SigHash::Single | SigHash::SingleAnyoneCanPay => {
+				let outputs = vec![transaction.outputs.get(index)?.clone()];
+				// commit to the index as well, so the signed output cannot be moved
+				let mut payload = Self::get_simple_transaction(&Transaction { inputs, outputs });
+				(index as u64).encode_to(&mut payload);
+				Some(payload)
+			}
+		}
 	}
 
 	/// Check transaction for validity, errors, & race conditions
 	/// Called by both transaction pool and runtime execution
 	///
 	/// Ensures that:
-	/// - inputs and outputs are not empty
-	/// - all inputs match to existing, unspent and unlocked outputs
+	/// - the chain is past its genesis state, so signatures commit to its genesis hash
+	/// - inputs and outputs are not empty, and at most `MAX_INPUTS` and `MAX_OUTPUTS`
+	/// - the weight of the transaction fits in a block
 	/// - each input is used exactly once
-	/// - each output is defined exactly once and has nonzero value
+	/// - each output is defined exactly once, has nonzero value and a valid lock
+	/// - all inputs match to existing, unspent and unlocked outputs
+	/// - input scripts are satisfied within `script::MAX_SCRIPT_DEPTH` and
+	///   `script::MAX_SCRIPT_WEIGHT`
+	/// - provided signatures are valid
 	/// - total output value must not exceed total input value
-	/// - new outputs do not collide with existing ones
 	/// - sum of input and output values does not overflow
-	/// - provided signatures are valid
+	/// - the fee pays at least `Trait::MinFeeRate`
+	/// - new outputs do not collide with existing ones
 	/// - transaction outputs cannot be modified by malicious nodes
-	pub fn validate_transaction(transaction: &Transaction) -> Result<ValidTransaction, &'static str> {
+	///
+	/// Inputs spending outputs that do not exist yet are not an error: the
+	/// transaction then requires their outpoints as pool tags, and the value
+	/// and fee checks wait until they exist.
+	pub fn validate_transaction(transaction: &Transaction) -> Result<ValidTransaction, Error<T>> {
 		// Check basic requirements
-		ensure!(!transaction.inputs.is_empty(), "no inputs");
-		ensure!(!transaction.outputs.is_empty(), "no outputs");
+		ensure!(Self::genesis_hash_known(), Error::<T>::GenesisHashUnknown);
+		ensure!(!transaction.inputs.is_empty(), Error::<T>::EmptyInputs);
+		ensure!(!transaction.outputs.is_empty(), Error::<T>::EmptyOutputs);
+		ensure!(transaction.inputs.len() <= MAX_INPUTS, Error::<T>::TooManyInputs);
+		ensure!(transaction.outputs.len() <= MAX_OUTPUTS, Error::<T>::TooManyOutputs);
+		// decoding bounds the depth of revealed scripts already, this guards
+		// transactions built in the runtime before they are encoded or hashed
+		ensure!(
+			transaction.inputs.iter()
+				.filter_map(|input| input.script.as_ref())
+				.all(|script| script.fits_depth(script::MAX_SCRIPT_DEPTH)),
+			Error::<T>::ScriptTooExpensive
+		);
+		let weight = spend_weight(transaction);
+		{
+			let budget = <T as system::Trait>::AvailableBlockRatio::get()
+				* <T as system::Trait>::MaximumBlockWeight::get();
+			ensure!(weight <= budget, Error::<T>::TooHeavy);
+		}
 
 		{
-			let input_set: BTreeMap<_, ()> =transaction.inputs.iter().map(|input| (input, ())).collect();
-			ensure!(input_set.len() == transaction.inputs.len(), "each input must only be used once");
+			let input_set: BTreeMap<_, ()> = transaction.inputs.iter().map(|input| (input.outpoint, ())).collect();
+			ensure!(input_set.len() == transaction.inputs.len(), Error::<T>::DuplicateInput);
 		}
 		{
 			let output_set: BTreeMap<_, ()> = transaction.outputs.iter().map(|output| (output, ())).collect();
-			ensure!(output_set.len() == transaction.outputs.len(), "each output must be defined only once");
+			ensure!(output_set.len() == transaction.outputs.len(), Error::<T>::DuplicateOutput);
 		}
 
 		let mut total_input: Value = 0;
 		let mut total_output: Value = 0;
 		let mut output_index: u64 = 0;
+		let txid = Self::txid(transaction);
 		let simple_transaction = Self::get_simple_transaction(transaction);
+		let current_block = <system::Module<T>>::block_number().saturated_into::<BlockNumber>();
+		let unlocked = |height: BlockNumber| height <= current_block;
 
 		// Variables sent to transaction pool
 		let mut missing_utxos = Vec::new();
 		let mut new_utxos = Vec::new();
 		let mut reward = 0;
 
-
 		// Check that inputs are valid
-		for input in transaction.inputs.iter() {
+		for (index, input) in transaction.inputs.iter().enumerate() {
 			if let Some(input_utxo) = <UtxoStore>::get(&input.outpoint) {
-				ensure!(sp_io::crypto::sr25519_verify(
-					&Signature::from_raw(*input.sigscript.as_fixed_bytes()),
-					&simple_transaction,
-					&Public::from_h256(input_utxo.pubkey)
-				), "signature must be valid" );
-				total_input = total_input.checked_add(input_utxo.value).ok_or("input value overflow")?;
+				let spending_script = Self::spending_script(&input_utxo.lock, input)?;
+				let payload = match input.sighash {
+					SigHash::All => Cow::Borrowed(&simple_transaction[..]),
+					_ => Cow::Owned(Self::signing_payload(transaction, index).ok_or(Error::<T>::NoSignedOutput)?),
+				};
+				let context = script::Context { payload: &payload, sequence: input.sequence };
+				let evaluation = script::evaluate(&spending_script, &input.sigscript, &context)
+					.map_err(|e| match e {
+						ScriptError::BadSignature => Error::<T>::BadSignature,
+						ScriptError::TooExpensive | ScriptError::TooDeep => Error::<T>::ScriptTooExpensive,
+						_ => Error::<T>::ScriptFailed,
+					})?;
+				// `spend_weight` charged the worst case of this input
+				ensure!(evaluation.weight <= script_weight(input), Error::<T>::ScriptTooExpensive);
+				total_input = total_input.checked_add(input_utxo.value).ok_or(Error::<T>::ValueOverflow)?;
+
+				ensure!(evaluation.unlock_at.map_or(true, unlocked), Error::<T>::OutputLocked);
+				ensure!(input_utxo.lock_until.map_or(true, unlocked), Error::<T>::OutputLocked);
+				ensure!(
+					unlocked(<UtxoCreated>::get(&input.outpoint).saturating_add(input.sequence)),
+					Error::<T>::OutputLocked
+				);
 			} else {
 				missing_utxos.push(input.outpoint.clone().as_fixed_bytes().to_vec());
 			}
@@ -183,112 +749,406 @@ impl<T: Trait> Module<T> {
 
 		// Check that outputs are valid
 		for output in transaction.outputs.iter() {
-			ensure!(output.value > 0, "output value must be nonzero");
-			let hash = BlakeTwo256::hash_of(&(&transaction.encode(), output_index));
-			output_index = output_index.checked_add(1).ok_or("output index overflow")?;
-			ensure!(!<UtxoStore>::contains_key(hash), "output already exists");
-			total_output = total_output.checked_add(output.value).ok_or("output value overflow")?;
+			ensure!(output.value > 0, Error::<T>::ZeroValueOutput);
+			if let Lock::MultiSig { threshold, pubkeys } = &output.lock {
+				ensure!(*threshold > 0 && *threshold as usize <= pubkeys.len(), Error::<T>::InvalidLock);
+			}
+			let hash = BlakeTwo256::hash_of(&(txid, output_index));
+			output_index = output_index.checked_add(1).ok_or(Error::<T>::OutputIndexOverflow)?;
+			ensure!(!<UtxoStore>::contains_key(hash), Error::<T>::OutputExists);
+			total_output = total_output.checked_add(output.value).ok_or(Error::<T>::ValueOverflow)?;
 			new_utxos.push(hash.as_fixed_bytes().to_vec());
 		}
 
 		// If no race condition, check the math
 		if missing_utxos.is_empty() {
-			ensure!( total_input >= total_output, "output value must not exceed input value");
-			reward = total_input.checked_sub(total_output).ok_or("reward underflow")?;
+			ensure!( total_input >= total_output, Error::<T>::InsufficientInput);
+			reward = total_input.checked_sub(total_output).ok_or(Error::<T>::RewardUnderflow)?;
+			// fee >= rate * weight / FEE_RATE_WEIGHT, rearranged to avoid rounding
+			let minimum = T::MinFeeRate::get().saturating_mul(weight as Value);
+			ensure!(reward.saturating_mul(FEE_RATE_WEIGHT as Value) >= minimum, Error::<T>::FeeTooLow);
 		}
 
+		// Conflicting spends of the same outpoints provide the same tags
+		let mut provides = new_utxos;
+		provides.extend(transaction.inputs.iter().map(|input| Self::spend_tag(&input.outpoint)));
+
 		Ok(ValidTransaction {
 			requires: missing_utxos,
-			provides: new_utxos,
-			priority: reward as u64,
+			provides,
+			priority: Self::fee_rate(reward, weight).saturated_into::<u64>(),
 			longevity: TransactionLongevity::max_value(),
 			propagate: true,
 		})
 		
 	}
 
+	/// Fee paid per `FEE_RATE_WEIGHT` of weight
+	pub fn fee_rate(fee: Value, weight: Weight) -> Value {
+		fee.saturating_mul(FEE_RATE_WEIGHT as Value) / weight.max(1) as Value
+	}
+
+	/// Whether `replacement` may replace the unconfirmed `original`, with
+	/// which it shares an input: it has to pay a higher fee and a higher fee
+	/// rate. Conflicting spends provide the same `spend_tag` and the pool keeps
+	/// the one with the higher priority, the fee rate, so the pool enforces
+	/// the fee rate part of this rule only.
+	pub fn can_replace(original: &Transaction, replacement: &Transaction) -> bool {
+		let conflicts = replacement.inputs.iter()
+			.any(|input| original.inputs.iter().any(|spent| spent.outpoint == input.outpoint));
+		let (original_fee, replacement_fee) = (Self::fee(original), Self::fee(replacement));
+
+		conflicts
+			&& replacement_fee > original_fee
+			&& Self::fee_rate(replacement_fee, spend_weight(replacement))
+				> Self::fee_rate(original_fee, spend_weight(original))
+	}
+
+
+
+	/// Fee a transaction leaves for the reward, counting only inputs that exist
+	pub fn fee(transaction: &Transaction) -> Value {
+		let total_input = transaction.inputs.iter()
+			.filter_map(|input| <UtxoStore>::get(&input.outpoint))
+			.fold(0, |total: Value, utxo| total.saturating_add(utxo.value));
+		let total_output = transaction.outputs.iter()
+			.fold(0, |total: Value, output| total.saturating_add(output.value));
+		total_input.saturating_sub(total_output)
+	}
+
+	/// Transaction id: hash of the transaction without its `sigscript`
+	/// witnesses, so that relaying it with other valid signatures keeps the id
+	pub fn txid(transaction: &Transaction) -> H256 {
+		BlakeTwo256::hash_of(&Self::strip_witnesses(transaction))
+	}
+
+	/// Hash of the whole transaction, including its `sigscript` witnesses
+	pub fn witness_hash(transaction: &Transaction) -> H256 {
+		BlakeTwo256::hash_of(transaction)
+	}
+
+	/// Pool tag provided by every transaction spending `outpoint`
+	pub fn spend_tag(outpoint: &H256) -> Vec<u8> {
+		let mut tag = SPEND_TAG.to_vec();
+		tag.extend(outpoint.as_bytes());
+		tag
+	}
+
+	/// Outpoint of the output at `index` of `transaction`, i.e. the hash of
+	/// the transaction id and the position of the output in it
+	pub fn outpoint(transaction: &Transaction, index: u64) -> H256 {
+		BlakeTwo256::hash_of(&(Self::txid(transaction), index))
+	}
+
+	/// Outpoints of all outputs the transaction creates, in output order
+	pub fn compute_outpoints(transaction: &Transaction) -> Vec<H256> {
+		let txid = Self::txid(transaction);
+		(0..transaction.outputs.len() as u64)
+			.map(|index| BlakeTwo256::hash_of(&(txid, index)))
+			.collect()
+	}
+
+	/// Look up an unspent output by its outpoint
+	pub fn utxo(outpoint: &H256) -> Option<TransactionOutput> {
+		<UtxoStore>::get(outpoint)
+	}
+
+	/// Script that has to be satisfied to spend an output with `lock`.
+	/// For `Lock::ScriptHash` it is the script revealed by `input`.
+	pub fn spending_script(lock: &Lock, input: &TransactionInput) -> Result<Script, Error<T>> {
+		match lock {
+			Lock::ScriptHash(hash) => match &input.script {
+				Some(script) if BlakeTwo256::hash_of(script) == *hash => Ok(script.clone()),
+				_ => Err(Error::<T>::ScriptMismatch),
+			},
+			_ => lock.script().ok_or(Error::<T>::ScriptMismatch),
+		}
+	}
+
+	/// Preimages revealed by inputs claiming `Lock::Htlc` outputs,
+	/// along with the claimed outpoints
+	fn htlc_preimages(transaction: &Transaction) -> Vec<(H256, Vec<u8>)> {
+		transaction.inputs.iter()
+			.filter(|input| match <UtxoStore>::get(&input.outpoint) {
+				Some(TransactionOutput { lock: Lock::Htlc { .. }, .. }) => true,
+				_ => false,
+			})
+			.filter_map(|input| match &input.sigscript[..] {
+				[branch, preimage, _] if branch[..] == [0] => Some((input.outpoint, preimage.clone())),
+				_ => None,
+			})
+			.collect()
+	}
+
+	/// Outpoints of every unspent output whose lock involves `pubkey`,
+	/// including multisig outputs shared with other keys
+	pub fn outpoints_of(pubkey: &H256) -> Vec<H256> {
+		<UtxoOwners>::iter_prefix(pubkey).collect()
+	}
+
+	/// Total value of the unspent outputs `pubkey` can spend alone at the
+	/// current height. Outputs that need other keys or a preimage, or that
+	/// are still time locked, are left out.
+	pub fn balance_of(pubkey: &H256) -> Value {
+		let current_block = <system::Module<T>>::block_number().saturated_into::<BlockNumber>();
+		<UtxoOwners>::iter_prefix(pubkey)
+			.filter_map(|outpoint| <UtxoStore>::get(outpoint))
+			.filter(|utxo| utxo.lock.spendable_by(pubkey, current_block))
+			.filter(|utxo| utxo.lock_until.map_or(true, |height| height <= current_block))
+			.fold(0, |total: Value, utxo| total.saturating_add(utxo.value))
+	}
+
 	/// Update storage to reflect changes made by transaction
-	/// Where each utxo key is a hash of the entire transaction and its order in the TransactionOutputs vector
+	/// Where each utxo key is the outpoint of the output: the hash of the
+	/// transaction id and the output's index in the TransactionOutputs vector
 	fn update_storage(transaction: &Transaction, reward: Value) -> DispatchResult {
 		// Calculate new reward total
 		let new_total = <RewardTotal>::get()
 			.checked_add(reward)
-			.ok_or("Reward overflow")?;
+			.ok_or(Error::<T>::RewardOverflow)?;
 		<RewardTotal>::put(new_total);
+		Self::deposit_event(Event::FeePaid(reward));
 
 		// Removing spent UTXOs
 		for input in &transaction.inputs {
-			<UtxoStore>::remove(input.outpoint);
+			Self::remove_utxo(&input.outpoint);
 		}
 
+		let txid = Self::txid(transaction);
 		let mut index: u64 = 0;
 		for output in &transaction.outputs {
-			let hash = BlakeTwo256::hash_of(&(&transaction.encode(), index));
-			index = index.checked_add(1).ok_or("output index overflow")?;
-			<UtxoStore>::insert(hash, output);
+			let hash = BlakeTwo256::hash_of(&(txid, index));
+			index = index.checked_add(1).ok_or(Error::<T>::OutputIndexOverflow)?;
+			Self::insert_utxo(hash, output.clone());
 		}
 		Ok(())
 	}
 
-	/// Redistribute combined reward value to block Author
-	fn disperse_reward(authorities: &[H256]) {
-		//1. devide reward fairly
-		let reward = <RewardTotal>::take();
-		let shared_value: Value = reward
-			.checked_div(authorities.len() as Value)
-			.ok_or("No Authorities")
-			.unwrap();
+	/// Record where `transaction` is included, if the transaction index is enabled
+	fn index_transaction(transaction: &Transaction) {
+		if T::TxIndexDepth::get() == 0 {
+			return;
+		}
 
-		if shared_value == 0 { return }
+		let txid = Self::txid(transaction);
+		let block = <system::Module<T>>::block_number().saturated_into::<BlockNumber>();
+		let extrinsic_index = <system::Module<T>>::extrinsic_index().unwrap_or_default();
+		<TransactionIndex>::insert(txid, TransactionLocation { block, extrinsic_index });
+		<TransactionsAt>::mutate(block, |txids| txids.push(txid));
+	}
 
-		let remainder = reward
-			.checked_sub(shared_value * authorities.len() as Value)
-			.ok_or("Sub underflow")
-			.unwrap();
+	/// Drop the transactions included `TxIndexDepth` blocks before `now` from the index
+	fn prune_transaction_index(now: BlockNumber) {
+		let depth = T::TxIndexDepth::get();
+		if now < depth {
+			return;
+		}
+
+		for txid in <TransactionsAt>::take(now - depth) {
+			<TransactionIndex>::remove(txid);
+		}
+	}
+
+	/// Store a new UTXO, index it under its owner and record its creation height
+	pub(crate) fn insert_utxo(outpoint: H256, utxo: TransactionOutput) {
+		let current_block = <system::Module<T>>::block_number().saturated_into::<BlockNumber>();
+		<UtxoCreated>::insert(outpoint, current_block);
+		for owner in utxo.lock.owners() {
+			<UtxoOwners>::insert(owner, outpoint, outpoint);
+		}
+		Self::deposit_event(Event::UtxoCreated(outpoint, utxo.lock.clone(), utxo.value));
+		<UtxoStore>::insert(outpoint, utxo);
+	}
+
+	/// Remove a spent UTXO together with its owner index entry and creation height
+	fn remove_utxo(outpoint: &H256) {
+		if let Some(utxo) = <UtxoStore>::take(outpoint) {
+			for owner in utxo.lock.owners() {
+				<UtxoOwners>::remove(owner, outpoint);
+			}
+			Self::deposit_event(Event::UtxoSpent(*outpoint));
+		}
+		<UtxoCreated>::remove(outpoint);
+	}
+
+	/// Translate `UtxoStore` on chains that predate `Lock` and `lock_until`.
+	/// The single key of a legacy output becomes a `Lock::Key`.
+	fn upgrade_outputs() {
+		if <OutputsUpgraded>::get() { return }
+
+		<UtxoStore as IterableStorageMap<_, _>>::translate(|_, output: LegacyOutput| Some(TransactionOutput {
+			value: output.value,
+			lock: Lock::Key(output.pubkey),
+			lock_until: output.lock_until,
+		}));
+		<OutputsUpgraded>::put(true);
+	}
+
+	/// Back-fill `UtxoOwners` from `UtxoStore` on chains that predate the index
+	fn build_owner_index() {
+		if <OwnerIndexBuilt>::get() { return }
+
+		for (outpoint, utxo) in <UtxoStore as IterableStorageMap<_, _>>::iter() {
+			for owner in utxo.lock.owners() {
+				<UtxoOwners>::insert(owner, outpoint, outpoint);
+			}
+		}
+		<OwnerIndexBuilt>::put(true);
+	}
+
+	/// Compute `TotalSupply` on chains that predate it
+	fn track_supply() {
+		if <SupplyTracked>::get() { return }
+
+		let supply = <UtxoStore as IterableStorageMap<_, _>>::iter()
+			.fold(<RewardTotal>::get(), |total: Value, (_, utxo)| total.saturating_add(utxo.value));
+		<TotalSupply>::put(supply);
+		<SupplyTracked>::put(true);
+	}
 
-		<RewardTotal>::put(remainder as Value);
+	/// Add the subsidy of `block` to the reward to disperse
+	fn mint_subsidy(block: BlockNumber) {
+		let subsidy = T::Subsidy::subsidy(block);
+		if subsidy == 0 { return }
+
+		<RewardTotal>::mutate(|reward| *reward = reward.saturating_add(subsidy));
+		<TotalSupply>::mutate(|supply| *supply = supply.saturating_add(subsidy));
+		Self::deposit_event(Event::SubsidyMinted(subsidy));
+	}
+
+	/// Disperse the collected reward as `T::RewardPolicy` splits it among
+	/// the block `author` and the `authorities`. Value that is not handed
+	/// out stays in `RewardTotal` for the next block, so no reward is lost.
+	fn disperse_reward(author: Option<H256>, authorities: &[H256]) {
+		let mut remaining = <RewardTotal>::take();
+		let block = <system::Module<T>>::block_number().saturated_into::<BlockNumber>();
+		// rewards mature like any other absolute time lock
+		let lock_until = match T::CoinbaseMaturity::get() {
+			0 => None,
+			maturity => Some(block.saturating_add(maturity)),
+		};
+
+		// Create utxo per rewarded key
+		for (recipient, value) in T::RewardPolicy::split(remaining, author.as_ref(), authorities) {
+			// shares beyond the reward are ignored rather than minted
+			if value == 0 || value > remaining { continue }
 
-		//2. Create utxo per validator
-		for authority in authorities {
 			let utxo = TransactionOutput {
-				value: shared_value,
-				pubkey: *authority,
+				value,
+				lock: Lock::Key(recipient),
+				lock_until,
 			};
 
-			let hash = BlakeTwo256::hash_of(&(&utxo, 
-											<system::Module<T>>::block_number().saturated_into::<u64>())
-										);
-			if !<UtxoStore>::contains_key(hash) {
-				<UtxoStore>::insert(hash, utxo);
-				sp_runtime::print("Transaction reward sent to");
-				sp_runtime::print(hash.as_fixed_bytes() as &[u8]);
-			} else {
-				sp_runtime::print("Transaction reward wasted due to hash colission");
+			if let Some(hash) = Self::reward_outpoint(&utxo, block) {
+				remaining -= value;
+				Self::insert_utxo(hash, utxo);
+				Self::deposit_event(Event::RewardDistributed(recipient, hash, value));
 			}
 		}
+
+		<RewardTotal>::put(remaining);
+	}
+
+	/// Free outpoint for a reward output created at `block`. Rewards of the
+	/// same value to the same key in one block are told apart by a nonce.
+	fn reward_outpoint(utxo: &TransactionOutput, block: BlockNumber) -> Option<H256> {
+		(0..REWARD_OUTPOINT_ATTEMPTS)
+			.map(|nonce| match nonce {
+				0 => BlakeTwo256::hash_of(&(utxo, block)),
+				_ => BlakeTwo256::hash_of(&(utxo, block, nonce)),
+			})
+			.find(|hash| !<UtxoStore>::contains_key(hash))
+	}
+}
+
+impl<T: Trait> ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	/// Let the pool admit `spend` extrinsics on the strength of the UTXO
+	/// transaction alone, so that no account is needed to submit them
+	fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		match call {
+			Call::spend(transaction) => Self::validate_transaction(transaction).map_err(|e| {
+				// report the exact reason to the pool
+				sp_runtime::print(e.as_str());
+				match e {
+					// no transaction unlocks outputs, so the pool could not hold
+					// the spend back; wallets resubmit it once the lock expires
+					Error::<T>::OutputLocked => InvalidTransaction::Future.into(),
+					_ => InvalidTransaction::Custom(e.as_u8()).into(),
+				}
+			}),
+			_ => Err(InvalidTransaction::Call.into()),
+		}
 	}
 }
 
+sp_api::decl_runtime_apis! {
+	/// Read access to the UTXO set for clients
+	pub trait UtxoApi {
+		/// Unspent output stored under `outpoint`, if any
+		fn utxo(outpoint: H256) -> Option<TransactionOutput>;
+		/// Outpoints of every unspent output whose lock involves `pubkey`
+		fn outpoints_of(pubkey: H256) -> Vec<H256>;
+		/// Total value of the unspent outputs `pubkey` can spend alone at the current height
+		fn balance_of(pubkey: H256) -> Value;
+		/// Validate `transaction` against the current UTXO set without applying it.
+		/// On failure the name of the `Error` variant is returned as UTF-8 bytes.
+		fn dry_run(transaction: Transaction) -> Result<ValidTransaction, Vec<u8>>;
+		/// Outpoints the outputs of `transaction` will be stored under
+		fn compute_outpoints(transaction: Transaction) -> Vec<H256>;
+		/// Id of `transaction`, which does not cover its signatures
+		fn txid(transaction: Transaction) -> H256;
+		/// Hash of `transaction` including its signatures
+		fn witness_hash(transaction: Transaction) -> H256;
+		/// Block and extrinsic index of a transaction included in the last
+		/// `TxIndexDepth` blocks
+		fn transaction_location(txid: H256) -> Option<TransactionLocation>;
+		/// Value in existence, including fees and subsidies not yet dispersed
+		fn total_supply() -> Value;
+		/// Whether `replacement` pays enough to replace the unconfirmed `original`
+		fn can_replace(original: Transaction, replacement: Transaction) -> bool;
+		/// Bytes the owner of input `index` has to sign to authorize `transaction`,
+		/// as selected by the input's `SigHash`. `None` in the genesis state, see `signing_prefix`.
+		fn signing_payload(transaction: Transaction, index: u32) -> Option<Vec<u8>>;
+		/// Prefix of every signing payload, binding signatures to this chain and runtime version.
+		/// `None` in the genesis state, as the runtime learns the genesis hash with block 1.
+		fn signing_prefix() -> Option<Vec<u8>>;
+	}
+}
 
 /// Tests for this module
 #[cfg(test)]
 mod tests {
 	use super::*;
 
-	use frame_support::{assert_ok, assert_err, impl_outer_origin, parameter_types, weights::Weight};
-	use sp_runtime::{testing::Header, traits::IdentityLookup, Perbill};
+	use frame_support::{assert_ok, assert_err, impl_outer_event, impl_outer_origin, parameter_types, weights::Weight};
+	use frame_support::storage::unhashed::put_raw;
+	use sp_runtime::{testing::Header, traits::{IdentityLookup, OnFinalize, OnRuntimeUpgrade}, Perbill};
+	use std::cell::RefCell;
 	use sp_core::testing::{KeyStore, SR25519};
 	use sp_core::traits::KeystoreExt;
+	use sp_core::crypto::KeyTypeId;
 
 	impl_outer_origin! {
 		pub enum Origin for Test {}
 	}
 
+	mod utxo {
+		pub use crate::utxo::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for Test {
+			utxo,
+		}
+	}
+
 	#[derive(Clone, Eq, PartialEq)]
 	pub struct Test;
 	parameter_types! {
 			pub const BlockHashCount: u64 = 250;
-			pub const MaximumBlockWeight: Weight = 1024;
+			pub const MaximumBlockWeight: Weight = 10_000_000;
 			pub const MaximumBlockLength: u32 = 2 * 1024;
 			pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
 	}
@@ -302,7 +1162,7 @@ mod tests {
 		type AccountId = u64;
 		type Lookup = IdentityLookup<Self::AccountId>;
 		type Header = Header;
-		type Event = ();
+		type Event = TestEvent;
 		type BlockHashCount = BlockHashCount;
 		type MaximumBlockWeight = MaximumBlockWeight;
 		type MaximumBlockLength = MaximumBlockLength;
@@ -313,18 +1173,145 @@ mod tests {
 		type OnNewAccount = ();
 		type OnKilledAccount = ();
 	}
+	parameter_types! {
+		pub const TxIndexDepth: BlockNumber = 10;
+		pub const CoinbaseMaturity: BlockNumber = 3;
+	}
+
+	thread_local! {
+		static AUTHORITIES: RefCell<Vec<H256>> = RefCell::new(Vec::new());
+		static AUTHOR: RefCell<Option<H256>> = RefCell::new(None);
+		static SUBSIDY: RefCell<Value> = RefCell::new(0);
+		static MIN_FEE_RATE: RefCell<Value> = RefCell::new(0);
+	}
+
+	// Validator set tests can change with `set_authorities`
+	pub struct TestAuthorities;
+	impl AuthoritiesProvider for TestAuthorities {
+		fn authorities() -> Vec<H256> {
+			AUTHORITIES.with(|authorities| authorities.borrow().clone())
+		}
+	}
+
+	fn set_authorities(authorities: Vec<H256>) {
+		AUTHORITIES.with(|current| *current.borrow_mut() = authorities);
+	}
+
+	// Block author tests can change with `set_author`
+	pub struct TestAuthor;
+	impl BlockAuthor for TestAuthor {
+		fn block_author() -> Option<H256> {
+			AUTHOR.with(|author| *author.borrow())
+		}
+	}
+
+	fn set_author(author: Option<H256>) {
+		AUTHOR.with(|current| *current.borrow_mut() = author);
+	}
+
+	// Subsidy of every block, tests can change it with `set_subsidy`
+	pub struct TestSubsidy;
+	impl SubsidySchedule for TestSubsidy {
+		fn subsidy(_block: BlockNumber) -> Value {
+			SUBSIDY.with(|subsidy| *subsidy.borrow())
+		}
+	}
+
+	fn set_subsidy(subsidy: Value) {
+		SUBSIDY.with(|current| *current.borrow_mut() = subsidy);
+	}
+
+	// Minimum fee rate, `0` unless a test changes it with `set_min_fee_rate`
+	pub struct TestMinFeeRate;
+	impl Get<Value> for TestMinFeeRate {
+		fn get() -> Value {
+			MIN_FEE_RATE.with(|rate| *rate.borrow())
+		}
+	}
+
+	fn set_min_fee_rate(rate: Value) {
+		MIN_FEE_RATE.with(|current| *current.borrow_mut() = rate);
+	}
+
 	impl Trait for Test {
-		type Event = ();
+		type Event = TestEvent;
+		type TxIndexDepth = TxIndexDepth;
+		type Authorities = TestAuthorities;
+		type BlockAuthor = TestAuthor;
+		type RewardPolicy = TestPolicy;
+		type Subsidy = TestSubsidy;
+		type CoinbaseMaturity = CoinbaseMaturity;
+		type MinFeeRate = TestMinFeeRate;
+	}
+
+	// The author takes all if the test set one, otherwise authorities split evenly
+	pub struct TestPolicy;
+	impl RewardPolicy for TestPolicy {
+		fn split(reward: Value, author: Option<&H256>, authorities: &[H256]) -> Vec<(H256, Value)> {
+			match author {
+				Some(_) => AuthorTakesAll::split(reward, author, authorities),
+				None => EvenSplit::split(reward, author, authorities),
+			}
+		}
 	}
 
 	type Utxo = Module<Test>;
 
-	// need to manually import this crate since its no include by default
-	use hex_literal::hex;
+	// Key type of keys generated during a test, kept apart from Alice's key
+	const COSIGNER: KeyTypeId = KeyTypeId(*b"cosi");
 
 	const ALICE_PHRASE: &str = "news slush supreme milk chapter athlete soap sausage put clutch what kitten";
 
-	const GENESIS_UTXO: [u8; 32] = hex!("79eabcbd5ef6e958c6a7851b36da07691c19bda1835a08f875aa286911800999");
+	// Output Alice owns at genesis
+	fn genesis_output(alice: H256) -> TransactionOutput {
+		TransactionOutput {
+			value: 100,
+			lock: Lock::Key(alice),
+			..Default::default()
+		}
+	}
+
+	// Outpoint of the output Alice owns at genesis
+	fn genesis_utxo() -> H256 {
+		let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+		BlakeTwo256::hash_of(&genesis_output(H256::from(alice_pub_key)))
+	}
+
+	// Value of every unspent output whose lock involves `pubkey`, whether it can be spent yet or not
+	fn owned_value(pubkey: &H256) -> Value {
+		Utxo::outpoints_of(pubkey).iter().filter_map(Utxo::utxo).map(|utxo| utxo.value).sum()
+	}
+
+	// Alice's signature over `payload`, as a witness item
+	fn alice_signature(payload: &[u8]) -> Vec<u8> {
+		let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+		sp_io::crypto::sr25519_sign(SR25519, &alice_pub_key, payload).unwrap().0.to_vec()
+	}
+
+	// Signs the first input of `transaction` as the owner of a `Lock::Key` of Alice
+	fn sign_as_alice(transaction: &mut Transaction) {
+		let signature = alice_signature(&Utxo::get_simple_transaction(transaction));
+		transaction.inputs[0].sigscript = vec![signature];
+	}
+
+	// Alice paying `fee` from her genesis output, over `outputs` outputs of 1 and the rest to Bob
+	fn payment(fee: Value, outputs: u8) -> Transaction {
+		let mut transaction = Transaction {
+			inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+			outputs: (0..outputs).map(|i| TransactionOutput {
+				value: 1,
+				lock: Lock::Key(H256::repeat_byte(i)),
+				..Default::default()
+			}).collect(),
+		};
+		transaction.outputs.push(TransactionOutput {
+			value: 100 - fee - outputs as Value,
+			lock: Lock::Key(H256::repeat_byte(0xb0)),
+			..Default::default()
+		});
+		sign_as_alice(&mut transaction);
+		transaction
+	}
 
 	// This function basically just builds a genesis storage key/value store according to our desired mockup.
 	// We start each test by giving Alice 100 utxo to start with.
@@ -339,12 +1326,7 @@ mod tests {
 
 		t.top.extend(
 			GenesisConfig {
-				genesis_utxo: vec![
-					TransactionOutput {
-						value: 100,
-						pubkey: H256::from(alice_pub_key),
-					}
-				],
+				genesis_utxo: vec![genesis_output(H256::from(alice_pub_key))],
 				..Default::default()
 			}
 			.build_storage()
@@ -352,9 +1334,10 @@ mod tests {
 			.top,
 		);
 
-		// Print the values to get GENESIS_UTXO
 		let mut ext = sp_io::TestExternalities::from(t);
 		ext.register_extension(KeystoreExt(keystore));
+		// spends are only accepted once the genesis hash is known
+		ext.execute_with(|| system::Module::<Test>::set_block_number(1));
 		ext
 	}
 
@@ -366,24 +1349,25 @@ mod tests {
 			// Alice wants to send herself a new utxo of value 50.
 			let mut transaction = Transaction {
 				inputs: vec![TransactionInput {
-					outpoint: H256::from(GENESIS_UTXO),
-					sigscript: H512::zero(),
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
 				}],
 				outputs: vec![TransactionOutput {
 					value: 50,
-					pubkey: H256::from(alice_pub_key),
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
 				}],
 			};
 
-			let alice_signature = sp_io::crypto::sr25519_sign(SR25519, &alice_pub_key, &transaction.encode()).unwrap();
-			transaction.inputs[0].sigscript = H512::from(alice_signature);
-			let new_utxo_hash = BlakeTwo256::hash_of(&(&transaction.encode(), 0 as u64));
+			sign_as_alice(&mut transaction);
+			let new_utxo_hash = Utxo::outpoint(&transaction, 0);
 
 			// spend will be ok
-			assert_ok!(Utxo::spend(Origin::signed(0), transaction));
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
 
 			// old utxo is gone
-			assert!(!UtxoStore::contains_key(H256::from(GENESIS_UTXO)));
+			assert!(!UtxoStore::contains_key(genesis_utxo()));
 
 			// new utxo exists and value = 50
 			assert!(UtxoStore::contains_key(new_utxo_hash));
@@ -391,5 +1375,1352 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn test_unsigned_submission() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+
+			// the pool validates the spend without any account
+			let call = Call::<Test>::spend(transaction.clone());
+			assert_eq!(Utxo::validate_unsigned(&call), Ok(Utxo::validate_transaction(&transaction).unwrap()));
+
+			let mut forged = transaction.clone();
+			forged.outputs[0].value = 100;
+			assert_eq!(
+				Utxo::validate_unsigned(&Call::spend(forged.clone())),
+				Err(InvalidTransaction::Custom(Error::<Test>::BadSignature.as_u8()).into())
+			);
+
+			// nor is it admitted into a block, where unsigned extrinsics pay no fee
+			assert_eq!(
+				Utxo::pre_dispatch(&Call::spend(forged)),
+				Err(InvalidTransaction::Custom(Error::<Test>::BadSignature.as_u8()).into())
+			);
+			assert_eq!(Utxo::pre_dispatch(&call), Ok(()));
+
+			// spends are only dispatched unsigned
+			assert_err!(Utxo::spend(Origin::signed(0), transaction.clone()), sp_runtime::DispatchError::BadOrigin);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+		});
+	}
+
+	#[test]
+	fn attack_with_empty_transactions() {
+		new_test_ext().execute_with(|| {
+			assert_err!(
+				Utxo::spend(Origin::NONE, Transaction::default()),
+				Error::<Test>::EmptyInputs
+			);
+
+			assert_err!(
+				Utxo::spend(Origin::NONE, Transaction {
+					inputs: vec![TransactionInput::default()],
+					outputs: vec![],
+				}),
+				Error::<Test>::EmptyOutputs
+			);
+		});
+	}
+
+	#[test]
+	fn attack_with_invalid_signature() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![vec![0x42; 64]],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::BadSignature);
+		});
+	}
+
+	#[test]
+	fn attack_by_double_counting_input() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			// Alice spends her utxo twice to pay herself 150
+			let mut transaction = Transaction {
+				inputs: vec![
+					TransactionInput {
+						outpoint: genesis_utxo(),
+						sigscript: vec![],
+						..Default::default()
+					},
+					TransactionInput {
+						outpoint: genesis_utxo(),
+						sigscript: vec![],
+						..Default::default()
+					},
+				],
+				outputs: vec![TransactionOutput {
+					value: 150,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+
+			// sr25519 signatures are randomized, so the two inputs differ in their signature only
+			let payload = Utxo::get_simple_transaction(&transaction);
+			transaction.inputs[0].sigscript = vec![alice_signature(&payload)];
+			transaction.inputs[1].sigscript = vec![alice_signature(&payload)];
+			assert_ne!(transaction.inputs[0], transaction.inputs[1]);
+
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::DuplicateInput);
+		});
+	}
+
+	#[test]
+	fn attack_with_oversized_transactions() {
+		new_test_ext().execute_with(|| {
+			let input = |i: u64| TransactionInput { outpoint: BlakeTwo256::hash_of(&i), ..Default::default() };
+			let output = |i: u64| TransactionOutput { value: 1, lock: Lock::Key(BlakeTwo256::hash_of(&i)), ..Default::default() };
+
+			let transaction = Transaction {
+				inputs: (0..MAX_INPUTS as u64 + 1).map(input).collect(),
+				outputs: vec![output(0)],
+			};
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::TooManyInputs);
+
+			let transaction = Transaction {
+				inputs: vec![input(0)],
+				outputs: (0..MAX_OUTPUTS as u64 + 1).map(output).collect(),
+			};
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::TooManyOutputs);
+
+			// within the limits, but too heavy for a block of the mock runtime
+			let transaction = Transaction {
+				inputs: (0..200).map(input).collect(),
+				outputs: vec![output(0)],
+			};
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::TooHeavy);
+		});
+	}
+
+	#[test]
+	fn test_fee_rate_priority() {
+		new_test_ext().execute_with(|| {
+			// a small transaction paying 10
+			let small = payment(10, 0);
+			// a transaction paying 20, but with eighty outputs
+			let large = payment(20, 79);
+
+			let small_validity = Utxo::validate_transaction(&small).unwrap();
+			let large_validity = Utxo::validate_transaction(&large).unwrap();
+			assert_eq!(small_validity.priority, Utxo::fee_rate(10, spend_weight(&small)) as u64);
+			assert_eq!(large_validity.priority, Utxo::fee_rate(20, spend_weight(&large)) as u64);
+			// the pool prefers the smaller transaction despite its lower fee
+			assert!(small_validity.priority > large_validity.priority);
+
+			// a minimum fee rate between the two rejects the larger one
+			set_min_fee_rate(Utxo::fee_rate(20, spend_weight(&large)) + 1);
+			assert!(Utxo::validate_transaction(&small).is_ok());
+			assert_err!(Utxo::spend(Origin::NONE, large), Error::<Test>::FeeTooLow);
+
+			// fees are paid in full, not by their rate
+			assert_ok!(Utxo::spend(Origin::NONE, small));
+			assert_eq!(Utxo::reward_total(), 10);
+		});
+	}
+
+	#[test]
+	fn test_replace_by_fee() {
+		new_test_ext().execute_with(|| {
+			let stuck = payment(20, 40);
+			let bumped = payment(30, 0);
+			let stuck_validity = Utxo::validate_transaction(&stuck).unwrap();
+			let bumped_validity = Utxo::validate_transaction(&bumped).unwrap();
+
+			// both provide the tag of the spent outpoint, so the pool sees them conflict
+			let tag = Utxo::spend_tag(&genesis_utxo());
+			assert!(stuck_validity.provides.contains(&tag));
+			assert!(bumped_validity.provides.contains(&tag));
+			// and keeps the one with the higher fee rate
+			assert!(bumped_validity.priority > stuck_validity.priority);
+
+			assert!(Utxo::can_replace(&stuck, &bumped));
+			assert!(!Utxo::can_replace(&bumped, &stuck));
+			// neither a higher fee rate alone nor a higher fee alone is enough
+			assert!(!Utxo::can_replace(&stuck, &payment(10, 0)));
+			assert!(!Utxo::can_replace(&stuck, &payment(21, 70)));
+			// nor is a higher fee for a transaction that does not conflict
+			let mut unrelated = bumped.clone();
+			unrelated.inputs[0].outpoint = H256::repeat_byte(0x01);
+			assert!(!Utxo::can_replace(&stuck, &unrelated));
+
+			// only one of the conflicting spends can be included
+			assert_ok!(Utxo::spend(Origin::NONE, bumped));
+			assert_err!(Utxo::spend(Origin::NONE, stuck), Error::<Test>::MissingInputs);
+		});
+	}
+
+	#[test]
+	fn attack_with_zero_fee() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			set_min_fee_rate(1);
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::FeeTooLow);
+		});
+	}
+
+	#[test]
+	fn test_spend_weight() {
+		new_test_ext().execute_with(|| {
+			let transaction = |inputs: u64, outputs: u64, items: usize| Transaction {
+				inputs: (0..inputs).map(|i| TransactionInput {
+					outpoint: BlakeTwo256::hash_of(&i),
+					sigscript: vec![vec![0; 64]; items],
+					..Default::default()
+				}).collect(),
+				outputs: (0..outputs).map(|i| TransactionOutput { value: i as Value + 1, ..Default::default() }).collect(),
+			};
+
+			let single = spend_weight(&transaction(1, 1, 1));
+			assert!(single > SPEND_BASE_WEIGHT + INPUT_WEIGHT + OUTPUT_WEIGHT);
+			assert!(spend_weight(&transaction(2, 1, 1)) >= single + INPUT_WEIGHT + script::SIGNATURE_WEIGHT);
+			assert!(spend_weight(&transaction(1, 2, 1)) >= single + OUTPUT_WEIGHT);
+			assert!(spend_weight(&transaction(1, 1, 3)) >= single + 2 * script::SIGNATURE_WEIGHT);
+
+			// the scripts of stored locks are weighed without reading them
+			let htlc = Lock::Htlc { hash: HashLock::Blake2(H256::zero()), recipient: H256::zero(), sender: H256::zero(), timeout: 0 };
+			assert_eq!(htlc.script().map(|script| script.nodes()), Some(LOCK_SCRIPT_NODES));
+		});
+	}
+
+	#[test]
+	fn attack_by_over_spending() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 101,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+
+			sign_as_alice(&mut transaction);
+
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::InsufficientInput);
+		});
+	}
+
+	#[test]
+	fn attack_by_spending_missing_input() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: H256::repeat_byte(0xab),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+
+			// the pool may wait for the input to appear, dispatch must not
+			assert!(Utxo::validate_transaction(&transaction).is_ok());
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::MissingInputs);
+		});
+	}
+
+	#[test]
+	fn test_absolute_time_lock() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			// Alice locks 50 to herself until block 5
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					lock_until: Some(5),
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let locked_utxo = Utxo::outpoint(&transaction, 0);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: locked_utxo,
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 40,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+
+			// the pool reports the spend as future, dispatch rejects it
+			system::Module::<Test>::set_block_number(4);
+			assert_eq!(
+				Utxo::validate_unsigned(&Call::spend(transaction.clone())),
+				Err(InvalidTransaction::Future.into())
+			);
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::OutputLocked);
+
+			system::Module::<Test>::set_block_number(5);
+			assert!(Utxo::validate_transaction(&transaction).unwrap().requires.is_empty());
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+		});
+	}
+
+	#[test]
+	fn test_relative_time_lock() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			system::Module::<Test>::set_block_number(3);
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let new_utxo = Utxo::outpoint(&transaction, 0);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(Utxo::utxo_created(new_utxo), 3);
+
+			// spendable two blocks after creation
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: new_utxo,
+					sigscript: vec![],
+					sequence: 2,
+				}],
+				outputs: vec![TransactionOutput {
+					value: 40,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+
+			system::Module::<Test>::set_block_number(4);
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::OutputLocked);
+
+			system::Module::<Test>::set_block_number(5);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert!(!UtxoCreated::contains_key(new_utxo));
+		});
+	}
+
+	#[test]
+	fn test_multisig_lock() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let keys: Vec<_> = (0..3).map(|_| sp_io::crypto::sr25519_generate(COSIGNER, None)).collect();
+			let lock = Lock::MultiSig {
+				threshold: 2,
+				pubkeys: keys.iter().map(|key| H256::from(*key)).collect(),
+			};
+
+			// Alice moves her funds to a 2-of-3 multisig
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: lock.clone(),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let shared_utxo = Utxo::outpoint(&transaction, 0);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			for key in keys.iter() {
+				assert_eq!(Utxo::outpoints_of(&H256::from(*key)), vec![shared_utxo]);
+			}
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: shared_utxo,
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			let payload = Utxo::get_simple_transaction(&transaction);
+			let sign = |key| sp_io::crypto::sr25519_sign(COSIGNER, key, &payload).unwrap().0.to_vec();
+
+			// not enough signatures
+			transaction.inputs[0].sigscript = vec![sign(&keys[0]), vec![], vec![]];
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::BadSignature);
+
+			// signatures out of key order
+			transaction.inputs[0].sigscript = vec![sign(&keys[2]), vec![], sign(&keys[0])];
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::BadSignature);
+
+			// no item for a key that does not sign
+			transaction.inputs[0].sigscript = vec![sign(&keys[0]), sign(&keys[2])];
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::ScriptFailed);
+
+			transaction.inputs[0].sigscript = vec![sign(&keys[0]), vec![], sign(&keys[2])];
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert!(Utxo::outpoints_of(&H256::from(keys[1])).is_empty());
+		});
+	}
+
+	#[test]
+	fn attack_by_spending_partial_multisig() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let keys: Vec<_> = (0..50).map(|_| sp_io::crypto::sr25519_generate(COSIGNER, None)).collect();
+
+			// Alice moves her funds to a 1-of-50 multisig
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::MultiSig {
+						threshold: 1,
+						pubkeys: keys.iter().map(|key| H256::from(*key)).collect(),
+					},
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let shared_utxo = Utxo::outpoint(&transaction, 0);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+
+			// spent by the last key, whose signature is checked against that key only
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: shared_utxo,
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			let payload = Utxo::get_simple_transaction(&transaction);
+			let signature = sp_io::crypto::sr25519_sign(COSIGNER, &keys[49], &payload).unwrap();
+
+			// a lone signature cannot stand in for the keys that do not sign
+			transaction.inputs[0].sigscript = vec![signature.0.to_vec()];
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::ScriptFailed);
+
+			// so every key is paid for by the witness, whether the output exists or not
+			transaction.inputs[0].sigscript = vec![vec![]; 49];
+			transaction.inputs[0].sigscript.push(signature.0.to_vec());
+			let verification = LOCK_SCRIPT_NODES as Weight * script::OPCODE_WEIGHT + 50 * script::SIGNATURE_WEIGHT;
+			assert_eq!(script_weight(&transaction.inputs[0]), verification);
+			let mut unknown = transaction.clone();
+			unknown.inputs[0].outpoint = H256::repeat_byte(0x01);
+			assert_eq!(spend_weight(&unknown), spend_weight(&transaction));
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+		});
+	}
+
+	#[test]
+	fn test_script_hash_lock() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let secret = b"open sesame".to_vec();
+
+			// Alice can spend alone, anyone else has to know the secret and wait for block 10
+			let script = Script::Or(
+				Box::new(Script::Key(H256::from(alice_pub_key))),
+				Box::new(Script::And(
+					Box::new(Script::Blake2Preimage(BlakeTwo256::hash(&secret))),
+					Box::new(Script::After(10)),
+				)),
+			);
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::ScriptHash(BlakeTwo256::hash_of(&script)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let script_utxo = Utxo::outpoint(&transaction, 0);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: script_utxo,
+					sigscript: vec![vec![1], secret.clone()],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(H256::repeat_byte(0xb0)),
+					..Default::default()
+				}],
+			};
+
+			// script has to be revealed
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::ScriptMismatch);
+
+			// wrong preimage
+			transaction.inputs[0].script = Some(script.clone());
+			transaction.inputs[0].sigscript = vec![vec![1], b"open barley".to_vec()];
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::ScriptFailed);
+
+			// right preimage, but too early
+			transaction.inputs[0].sigscript = vec![vec![1], secret];
+			system::Module::<Test>::set_block_number(9);
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::OutputLocked);
+
+			system::Module::<Test>::set_block_number(10);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+		});
+	}
+
+	#[test]
+	fn attack_with_deeply_nested_script() {
+		new_test_ext().execute_with(|| {
+			let alice = H256::from(sp_io::crypto::sr25519_public_keys(SR25519)[0]);
+
+			// the branch Alice would not pick nests past the limit
+			let unused = (0..script::MAX_SCRIPT_DEPTH).fold(Script::After(0), |script, _| {
+				Script::And(Box::new(script), Box::new(Script::After(0)))
+			});
+			let script = Script::Or(Box::new(Script::Key(alice)), Box::new(unused));
+			let script_utxo = H256::repeat_byte(0x01);
+			Utxo::insert_utxo(script_utxo, TransactionOutput {
+				value: 100,
+				lock: Lock::ScriptHash(BlakeTwo256::hash_of(&script)),
+				..Default::default()
+			});
+
+			let transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: script_utxo,
+					sigscript: vec![vec![0], vec![0; 64]],
+					script: Some(script),
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(alice),
+					..Default::default()
+				}],
+			};
+
+			// submitted as an extrinsic, the spend does not even decode
+			assert!(Transaction::decode(&mut &transaction.encode()[..]).is_err());
+			// built in the runtime, it is rejected before it is encoded or hashed
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::ScriptTooExpensive);
+		});
+	}
+
+	#[test]
+	fn test_htlc_lock() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let bob_pub_key = sp_io::crypto::sr25519_generate(COSIGNER, None);
+			let secret = b"swap secret".to_vec();
+
+			// Alice offers 100 to Bob against the secret, refundable from block 20
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Htlc {
+						hash: HashLock::Sha256(H256::from(sp_io::hashing::sha2_256(&secret))),
+						recipient: H256::from(bob_pub_key),
+						sender: H256::from(alice_pub_key),
+						timeout: 20,
+					},
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let htlc_utxo = Utxo::outpoint(&transaction, 0);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(Utxo::outpoints_of(&H256::from(bob_pub_key)), vec![htlc_utxo]);
+
+			// Alice cannot take the refund before the timeout
+			let mut refund = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: htlc_utxo,
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			refund.inputs[0].sigscript = vec![vec![1], alice_signature(&Utxo::get_simple_transaction(&refund))];
+			system::Module::<Test>::set_block_number(19);
+			assert_err!(Utxo::spend(Origin::NONE, refund), Error::<Test>::OutputLocked);
+
+			// Bob claims with the secret
+			let mut claim = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: htlc_utxo,
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(H256::from(bob_pub_key)),
+					..Default::default()
+				}],
+			};
+			let bob_signature = sp_io::crypto::sr25519_sign(COSIGNER, &bob_pub_key, &Utxo::get_simple_transaction(&claim)).unwrap();
+			claim.inputs[0].sigscript = vec![vec![0], b"wrong secret".to_vec(), bob_signature.0.to_vec()];
+			assert_err!(Utxo::spend(Origin::NONE, claim.clone()), Error::<Test>::ScriptFailed);
+
+			claim.inputs[0].sigscript = vec![vec![0], secret.clone(), bob_signature.0.to_vec()];
+			assert_eq!(Utxo::htlc_preimages(&claim), vec![(htlc_utxo, secret)]);
+			assert_ok!(Utxo::spend(Origin::NONE, claim));
+			assert!(Utxo::outpoints_of(&H256::from(alice_pub_key)).is_empty());
+		});
+	}
+
+	#[test]
+	fn test_ed25519_and_ecdsa_keys() {
+		new_test_ext().execute_with(|| {
+			use sp_core::{Pair, ecdsa, ed25519};
+			use sp_runtime::{MultiSignature, MultiSigner, traits::IdentifyAccount};
+
+			let ed25519 = ed25519::Pair::from_string("//Bob", None).unwrap();
+			let ecdsa = ecdsa::Pair::from_string("//Charlie", None).unwrap();
+			let account = |signer: MultiSigner| H256::from_slice(signer.into_account().as_ref());
+			let ed25519_key = account(ed25519.public().into());
+			let ecdsa_key = account(ecdsa.public().into());
+
+			// Alice pays an ed25519 and an ECDSA key
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					..Default::default()
+				}],
+				outputs: vec![
+					TransactionOutput { value: 50, lock: Lock::Key(ed25519_key), ..Default::default() },
+					TransactionOutput { value: 50, lock: Lock::Key(ecdsa_key), ..Default::default() },
+				],
+			};
+
+			sign_as_alice(&mut transaction);
+			let ed25519_utxo = Utxo::outpoint(&transaction, 0);
+			let ecdsa_utxo = Utxo::outpoint(&transaction, 1);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(Utxo::balance_of(&ecdsa_key), 50);
+
+			// both spend their output together, each signing with its own scheme
+			let mut transaction = Transaction {
+				inputs: vec![
+					TransactionInput { outpoint: ed25519_utxo, ..Default::default() },
+					TransactionInput { outpoint: ecdsa_utxo, ..Default::default() },
+				],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::Key(ed25519_key),
+					..Default::default()
+				}],
+			};
+			let payload = Utxo::get_simple_transaction(&transaction);
+			let ed25519_signature = MultiSignature::from(ed25519.sign(&payload)).encode();
+			let ecdsa_signature = MultiSignature::from(ecdsa.sign(&payload)).encode();
+
+			// a signature of the wrong scheme is rejected
+			transaction.inputs[0].sigscript = vec![ed25519_signature.clone()];
+			transaction.inputs[1].sigscript = vec![ed25519_signature.clone()];
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::BadSignature);
+
+			transaction.inputs[1].sigscript = vec![ecdsa_signature];
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(Utxo::balance_of(&ed25519_key), 100);
+			assert_eq!(Utxo::balance_of(&ecdsa_key), 0);
+		});
+	}
+
+	#[test]
+	fn attack_with_unspendable_multisig() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 100,
+					lock: Lock::MultiSig { threshold: 2, pubkeys: vec![H256::from(alice_pub_key)] },
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+
+			assert_err!(Utxo::spend(Origin::NONE, transaction), Error::<Test>::InvalidLock);
+		});
+	}
+
+	#[test]
+	fn test_sighash_anyone_can_pay() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let bob_pub_key = sp_io::crypto::sr25519_generate(COSIGNER, None);
+			let charlie = H256::repeat_byte(0xc0);
+
+			// Alice gives 40 to Bob
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![
+					TransactionOutput { value: 60, lock: Lock::Key(H256::from(alice_pub_key)), ..Default::default() },
+					TransactionOutput { value: 40, lock: Lock::Key(H256::from(bob_pub_key)), ..Default::default() },
+				],
+			};
+			sign_as_alice(&mut transaction);
+			let outpoints = Utxo::compute_outpoints(&transaction);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+
+			// Alice pledges her output to Charlie's crowdfund before anyone else did
+			let mut crowdfund = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: outpoints[0],
+					sighash: SigHash::AllAnyoneCanPay,
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput { value: 100, lock: Lock::Key(charlie), ..Default::default() }],
+			};
+			let payload = Utxo::signing_payload(&crowdfund, 0).unwrap();
+			crowdfund.inputs[0].sigscript = vec![alice_signature(&payload)];
+			assert_err!(Utxo::spend(Origin::NONE, crowdfund.clone()), Error::<Test>::InsufficientInput);
+
+			// Bob completes it without Alice signing again
+			crowdfund.inputs.push(TransactionInput { outpoint: outpoints[1], ..Default::default() });
+			let payload = Utxo::signing_payload(&crowdfund, 1).unwrap();
+			let bob_signature = sp_io::crypto::sr25519_sign(COSIGNER, &bob_pub_key, &payload).unwrap();
+			crowdfund.inputs[1].sigscript = vec![bob_signature.0.to_vec()];
+
+			// the outputs are still committed to
+			let mut redirected = crowdfund.clone();
+			redirected.outputs[0].lock = Lock::Key(H256::from(bob_pub_key));
+			assert_err!(Utxo::spend(Origin::NONE, redirected), Error::<Test>::BadSignature);
+
+			// had Alice signed all inputs, Bob's input would break her signature
+			let mut committed = crowdfund.clone();
+			committed.inputs[0].sighash = SigHash::All;
+			assert_err!(Utxo::spend(Origin::NONE, committed), Error::<Test>::BadSignature);
+
+			assert_ok!(Utxo::spend(Origin::NONE, crowdfund));
+			assert_eq!(Utxo::balance_of(&charlie), 100);
+		});
+	}
+
+	#[test]
+	fn test_sighash_single() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let bob = H256::repeat_byte(0xb0);
+
+			// Alice only binds the change returned to her
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sighash: SigHash::Single,
+					..Default::default()
+				}],
+				outputs: vec![
+					TransactionOutput { value: 60, lock: Lock::Key(H256::from(alice_pub_key)), ..Default::default() },
+				],
+			};
+			let payload = Utxo::signing_payload(&transaction, 0).unwrap();
+			transaction.inputs[0].sigscript = vec![alice_signature(&payload)];
+
+			// changing the bound output breaks the signature
+			let mut changed = transaction.clone();
+			changed.outputs[0].value = 50;
+			assert_err!(Utxo::spend(Origin::NONE, changed), Error::<Test>::BadSignature);
+
+			// an input without an output at its index has nothing to sign
+			let mut unmatched = transaction.clone();
+			unmatched.inputs.insert(0, TransactionInput { outpoint: H256::repeat_byte(0x01), ..Default::default() });
+			assert_eq!(Utxo::signing_payload(&unmatched, 1), None);
+			assert_err!(Utxo::spend(Origin::NONE, unmatched), Error::<Test>::NoSignedOutput);
+
+			// but any output can be added after it
+			transaction.outputs.push(TransactionOutput { value: 40, lock: Lock::Key(bob), ..Default::default() });
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(Utxo::balance_of(&bob), 40);
+			assert_eq!(Utxo::balance_of(&H256::from(alice_pub_key)), 60);
+		});
+	}
+
+	#[test]
+	fn test_outpoints_and_signing_payload() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![
+					TransactionOutput { value: 30, lock: Lock::Key(H256::from(alice_pub_key)), ..Default::default() },
+					TransactionOutput { value: 20, lock: Lock::Key(H256::from(alice_pub_key)), ..Default::default() },
+				],
+			};
+			let payload = Utxo::get_simple_transaction(&transaction);
+
+			transaction.inputs[0].sigscript = vec![alice_signature(&payload)];
+
+			// signing payload ignores the signatures
+			assert_eq!(Utxo::get_simple_transaction(&transaction), payload);
+
+			let outpoints = Utxo::compute_outpoints(&transaction);
+			assert_eq!(outpoints, vec![
+				Utxo::outpoint(&transaction, 0),
+				Utxo::outpoint(&transaction, 1),
+			]);
+
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(30, UtxoStore::get(outpoints[0]).unwrap().value);
+			assert_eq!(20, UtxoStore::get(outpoints[1]).unwrap().value);
+		});
+	}
+
+	#[test]
+	fn test_signing_payload_is_chain_bound() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			// the genesis state only holds a placeholder for the genesis hash
+			<system::Module<Test>>::set_block_number(0);
+			assert!(!Utxo::genesis_hash_known());
+			sign_as_alice(&mut transaction);
+			assert_err!(Utxo::validate_transaction(&transaction), Error::<Test>::GenesisHashUnknown);
+			<system::Module<Test>>::set_block_number(1);
+			assert!(Utxo::genesis_hash_known());
+
+			let payload = Utxo::get_simple_transaction(&transaction);
+			assert!(payload.starts_with(SIGNING_TAG));
+			assert_eq!(payload, [Utxo::signing_prefix(), transaction.encode()].concat());
+
+			// a signature over the bare transaction is not accepted
+			transaction.inputs[0].sigscript = vec![alice_signature(&transaction.encode())];
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::BadSignature);
+
+			// nor is one made for a chain with another genesis
+			transaction.inputs[0].sigscript = vec![alice_signature(&payload)];
+			let genesis_hash = <system::Module<Test>>::block_hash(0);
+			<system::BlockHash<Test>>::insert(0, H256::repeat_byte(0x01));
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::BadSignature);
+
+			<system::BlockHash<Test>>::insert(0, genesis_hash);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+		});
+	}
+
+	#[test]
+	fn test_txid_ignores_witnesses() {
+		new_test_ext().execute_with(|| {
+			use sp_runtime::MultiSignature;
+
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			let alice_signature = sp_io::crypto::sr25519_sign(SR25519, &alice_pub_key, &Utxo::get_simple_transaction(&transaction)).unwrap();
+
+			// the same signature encoded in two valid ways
+			let mut relayed = transaction.clone();
+			transaction.inputs[0].sigscript = vec![alice_signature.0.to_vec()];
+			relayed.inputs[0].sigscript = vec![MultiSignature::from(alice_signature).encode()];
+
+			assert_eq!(Utxo::txid(&transaction), Utxo::txid(&relayed));
+			assert_ne!(Utxo::witness_hash(&transaction), Utxo::witness_hash(&relayed));
+			assert_eq!(Utxo::compute_outpoints(&transaction), Utxo::compute_outpoints(&relayed));
+
+			// spending the same outpoint twice is caught even with different witnesses
+			let mut doubled = transaction.clone();
+			doubled.inputs.push(relayed.inputs[0].clone());
+			assert_err!(Utxo::spend(Origin::NONE, doubled), Error::<Test>::DuplicateInput);
+
+			// whichever encoding gets relayed, children built on the outpoint stay valid
+			let outpoint = Utxo::compute_outpoints(&transaction)[0];
+			assert_ok!(Utxo::spend(Origin::NONE, relayed));
+			assert_eq!(Utxo::utxo(&outpoint).unwrap().value, 50);
+		});
+	}
+
+	#[test]
+	fn test_transaction_index() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			<system::Module<Test>>::set_block_number(5);
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let txid = Utxo::txid(&transaction);
+
+			assert_eq!(Utxo::transaction_location(txid), None);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(Utxo::transaction_location(txid), Some(TransactionLocation { block: 5, extrinsic_index: 0 }));
+
+			// kept for `TxIndexDepth` blocks
+			Utxo::prune_transaction_index(5 + TxIndexDepth::get() - 1);
+			assert!(Utxo::transaction_location(txid).is_some());
+			Utxo::prune_transaction_index(5 + TxIndexDepth::get());
+			assert_eq!(Utxo::transaction_location(txid), None);
+		});
+	}
+
+	#[test]
+	fn test_events() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let authority = H256::repeat_byte(0xa0);
+			// events are not recorded at genesis
+			<system::Module<Test>>::set_block_number(1);
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![TransactionOutput {
+					value: 60,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+			let outpoint = Utxo::outpoint(&transaction, 0);
+
+			assert_ok!(Utxo::spend(Origin::NONE, transaction.clone()));
+			Utxo::disperse_reward(Some(authority), &[]);
+			let reward_outpoint = Utxo::outpoints_of(&authority)[0];
+
+			let events: Vec<_> = <system::Module<Test>>::events().into_iter().map(|record| record.event).collect();
+			assert_eq!(events, vec![
+				TestEvent::utxo(Event::FeePaid(40)),
+				TestEvent::utxo(Event::UtxoSpent(genesis_utxo())),
+				TestEvent::utxo(Event::UtxoCreated(outpoint, Lock::Key(H256::from(alice_pub_key)), 60)),
+				TestEvent::utxo(Event::TransactionSuccess(transaction)),
+				TestEvent::utxo(Event::UtxoCreated(reward_outpoint, Lock::Key(authority), 40)),
+				TestEvent::utxo(Event::RewardDistributed(authority, reward_outpoint, 40)),
+			]);
+		});
+	}
+
+	#[test]
+	fn test_fees_rewarded_to_block_author() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let authorities = vec![H256::repeat_byte(0xa0), H256::repeat_byte(0xa1)];
+			set_authorities(authorities.clone());
+			set_author(Some(authorities[1]));
+			<system::Module<Test>>::set_block_number(1);
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: genesis_utxo(), ..Default::default() }],
+				outputs: vec![TransactionOutput {
+					value: 60,
+					lock: Lock::Key(H256::from(alice_pub_key)),
+					..Default::default()
+				}],
+			};
+			sign_as_alice(&mut transaction);
+
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+			assert_eq!(Utxo::reward_total(), 40);
+
+			Utxo::on_finalize(1);
+			assert_eq!(Utxo::reward_total(), 0);
+			assert_eq!(owned_value(&authorities[0]), 0);
+			assert_eq!(owned_value(&authorities[1]), 40);
+		});
+	}
+
+	#[test]
+	fn test_reward_without_recipients_is_carried_over() {
+		new_test_ext().execute_with(|| {
+			<RewardTotal>::put(40);
+
+			// neither an author nor authorities to pay
+			Utxo::on_finalize(1);
+			assert_eq!(Utxo::reward_total(), 40);
+
+			set_author(Some(H256::repeat_byte(0xa0)));
+			Utxo::on_finalize(2);
+			assert_eq!(Utxo::reward_total(), 0);
+			assert_eq!(owned_value(&H256::repeat_byte(0xa0)), 40);
+		});
+	}
+
+	#[test]
+	fn test_reward_remainder_is_carried_over() {
+		new_test_ext().execute_with(|| {
+			let authorities = vec![H256::repeat_byte(0xa0), H256::repeat_byte(0xa1), H256::repeat_byte(0xa2)];
+			set_authorities(authorities.clone());
+			<RewardTotal>::put(100);
+
+			Utxo::on_finalize(1);
+			assert_eq!(Utxo::reward_total(), 1);
+			for authority in &authorities {
+				assert_eq!(owned_value(authority), 33);
+			}
+		});
+	}
+
+	#[test]
+	fn test_colliding_rewards_are_kept_apart() {
+		new_test_ext().execute_with(|| {
+			// the same key twice gets two identical reward outputs
+			let authority = H256::repeat_byte(0xa0);
+			set_authorities(vec![authority, authority]);
+			<RewardTotal>::put(100);
+
+			Utxo::on_finalize(1);
+			assert_eq!(Utxo::reward_total(), 0);
+			assert_eq!(Utxo::outpoints_of(&authority).len(), 2);
+			assert_eq!(owned_value(&authority), 100);
+
+			// without a free outpoint the reward waits for the next block
+			let utxo = TransactionOutput { value: 50, lock: Lock::Key(authority), lock_until: Some(1 + CoinbaseMaturity::get()) };
+			for nonce in 0..REWARD_OUTPOINT_ATTEMPTS {
+				let hash = match nonce {
+					0 => BlakeTwo256::hash_of(&(&utxo, 1 as BlockNumber)),
+					_ => BlakeTwo256::hash_of(&(&utxo, 1 as BlockNumber, nonce)),
+				};
+				<UtxoStore>::insert(hash, utxo.clone());
+			}
+			<RewardTotal>::put(100);
+			Utxo::on_finalize(1);
+			assert_eq!(Utxo::reward_total(), 100);
+		});
+	}
+
+	#[test]
+	fn test_subsidy_minted_to_block_author() {
+		new_test_ext().execute_with(|| {
+			let author = H256::repeat_byte(0xa0);
+			set_author(Some(author));
+			set_subsidy(25);
+			assert_eq!(Utxo::total_supply(), 100);
+
+			Utxo::on_finalize(1);
+			assert_eq!(owned_value(&author), 25);
+			assert_eq!(Utxo::total_supply(), 125);
+
+			// undispersed subsidy still counts towards the supply
+			set_author(None);
+			Utxo::on_finalize(2);
+			assert_eq!(Utxo::reward_total(), 25);
+			assert_eq!(Utxo::total_supply(), 150);
+		});
+	}
+
+	#[test]
+	fn test_halving_schedule() {
+		parameter_types! {
+			pub const InitialSubsidy: Value = 50;
+			pub const HalvingInterval: BlockNumber = 100;
+			pub const NoHalving: BlockNumber = 0;
+		}
+		type Schedule = Halving<InitialSubsidy, HalvingInterval>;
+
+		assert_eq!(Schedule::subsidy(0), 50);
+		assert_eq!(Schedule::subsidy(99), 50);
+		assert_eq!(Schedule::subsidy(100), 25);
+		assert_eq!(Schedule::subsidy(250), 12);
+		assert_eq!(Schedule::subsidy(600), 0);
+		assert_eq!(Schedule::subsidy(BlockNumber::max_value()), 0);
+		assert_eq!(Halving::<InitialSubsidy, NoHalving>::subsidy(BlockNumber::max_value()), 50);
+		assert_eq!(<() as SubsidySchedule>::subsidy(0), 0);
+	}
+
+	#[test]
+	fn test_total_supply_migration() {
+		new_test_ext().execute_with(|| {
+			<RewardTotal>::put(7);
+			<TotalSupply>::kill();
+			<SupplyTracked>::put(false);
+
+			Utxo::track_supply();
+			assert_eq!(Utxo::total_supply(), 107);
+		});
+	}
+
+	#[test]
+	fn test_coinbase_maturity() {
+		new_test_ext().execute_with(|| {
+			let author_pub_key = sp_io::crypto::sr25519_generate(COSIGNER, None);
+			let author = H256::from(author_pub_key);
+			set_author(Some(author));
+			<RewardTotal>::put(40);
+			<system::Module<Test>>::set_block_number(1);
+			Utxo::on_finalize(1);
+
+			let reward_utxo = Utxo::outpoints_of(&author)[0];
+			let matures_at = 1 + CoinbaseMaturity::get();
+			assert_eq!(Utxo::utxo(&reward_utxo).unwrap().lock_until, Some(matures_at));
+			// immature rewards are not part of the spendable balance
+			assert_eq!(Utxo::balance_of(&author), 0);
 
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput { outpoint: reward_utxo, ..Default::default() }],
+				outputs: vec![TransactionOutput { value: 40, lock: Lock::Key(author), ..Default::default() }],
+			};
+			let author_signature = sp_io::crypto::sr25519_sign(COSIGNER, &author_pub_key, &Utxo::get_simple_transaction(&transaction)).unwrap();
+			transaction.inputs[0].sigscript = vec![author_signature.0.to_vec()];
+
+			// the spend is rejected until the reward matures
+			<system::Module<Test>>::set_block_number(2);
+			assert_err!(Utxo::spend(Origin::NONE, transaction.clone()), Error::<Test>::OutputLocked);
+
+			<system::Module<Test>>::set_block_number(matures_at);
+			assert_eq!(Utxo::balance_of(&author), 40);
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+		});
+	}
+
+	#[test]
+	fn test_reward_policies() {
+		parameter_types! {
+			pub const Treasury: H256 = H256([0x7e; 32]);
+			pub const TreasuryShare: Percent = Percent::from_percent(20);
+		}
+		let authorities = [H256::repeat_byte(0xa0), H256::repeat_byte(0xa1), H256::repeat_byte(0xa2)];
+		let author = Some(&authorities[1]);
+
+		assert_eq!(AuthorTakesAll::split(100, author, &authorities), vec![(authorities[1], 100)]);
+		assert_eq!(AuthorTakesAll::split(100, None, &authorities), vec![]);
+
+		assert_eq!(
+			EvenSplit::split(100, author, &authorities),
+			vec![(authorities[0], 33), (authorities[1], 33), (authorities[2], 33)]
+		);
+		assert_eq!(EvenSplit::split(100, author, &[]), vec![]);
+
+		type WithTreasury = AuthorAndTreasury<Treasury, TreasuryShare>;
+		assert_eq!(
+			WithTreasury::split(100, author, &authorities),
+			vec![(Treasury::get(), 20), (authorities[1], 80)]
+		);
+		assert_eq!(WithTreasury::split(100, None, &authorities), vec![(Treasury::get(), 20)]);
+	}
+
+	#[test]
+	fn test_query_by_pubkey() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let alice = H256::from(alice_pub_key);
+
+			assert_eq!(Utxo::outpoints_of(&alice), vec![genesis_utxo()]);
+			assert_eq!(Utxo::balance_of(&alice), 100);
+			assert_eq!(Utxo::utxo(&genesis_utxo()).unwrap().lock, Lock::Key(alice));
+
+			assert!(Utxo::outpoints_of(&H256::zero()).is_empty());
+			assert_eq!(Utxo::balance_of(&H256::zero()), 0);
+		});
+	}
+
+	#[test]
+	fn test_balance_counts_spendable_outputs() {
+		new_test_ext().execute_with(|| {
+			let alice = H256::from(sp_io::crypto::sr25519_public_keys(SR25519)[0]);
+			let bob = H256::repeat_byte(0xb0);
+			let outputs = vec![
+				TransactionOutput { value: 10, lock: Lock::Key(alice), lock_until: Some(5) },
+				TransactionOutput { value: 20, lock: Lock::MultiSig { threshold: 2, pubkeys: vec![alice, bob] }, lock_until: None },
+				TransactionOutput { value: 30, lock: Lock::MultiSig { threshold: 1, pubkeys: vec![alice, bob] }, lock_until: None },
+				TransactionOutput {
+					value: 40,
+					lock: Lock::Htlc { hash: HashLock::Blake2(H256::zero()), recipient: bob, sender: alice, timeout: 5 },
+					lock_until: None,
+				},
+			];
+			for (i, output) in outputs.into_iter().enumerate() {
+				Utxo::insert_utxo(H256::repeat_byte(i as u8 + 1), output);
+			}
+
+			// every output involving a key is listed, but only those it can spend alone count
+			assert_eq!(Utxo::outpoints_of(&alice).len(), 5);
+			assert_eq!(Utxo::balance_of(&alice), 100 + 30);
+			assert_eq!(Utxo::balance_of(&bob), 30);
+
+			// the time locked output and the HTLC refund become spendable at block 5
+			<system::Module<Test>>::set_block_number(5);
+			assert_eq!(Utxo::balance_of(&alice), 100 + 10 + 30 + 40);
+			assert_eq!(Utxo::balance_of(&bob), 30);
+		});
+	}
+
+	#[test]
+	fn test_owner_index_follows_spends() {
+		new_test_ext().execute_with(|| {
+			let alice_pub_key = sp_io::crypto::sr25519_public_keys(SR25519)[0];
+			let bob = H256::repeat_byte(0xb0);
+
+			let mut transaction = Transaction {
+				inputs: vec![TransactionInput {
+					outpoint: genesis_utxo(),
+					sigscript: vec![],
+					..Default::default()
+				}],
+				outputs: vec![TransactionOutput {
+					value: 50,
+					lock: Lock::Key(bob),
+					..Default::default()
+				}],
+			};
+
+			sign_as_alice(&mut transaction);
+			let new_utxo_hash = Utxo::outpoint(&transaction, 0);
+
+			assert_ok!(Utxo::spend(Origin::NONE, transaction));
+
+			assert!(Utxo::outpoints_of(&H256::from(alice_pub_key)).is_empty());
+			assert_eq!(Utxo::outpoints_of(&bob), vec![new_utxo_hash]);
+			assert_eq!(Utxo::balance_of(&bob), 50);
+		});
+	}
+
+	#[test]
+	fn test_owner_index_migration() {
+		new_test_ext().execute_with(|| {
+			let alice = H256::from(sp_io::crypto::sr25519_public_keys(SR25519)[0]);
+
+			// pretend the chain was started before the index existed
+			UtxoOwners::remove_prefix(alice);
+			OwnerIndexBuilt::put(false);
+			assert!(Utxo::outpoints_of(&alice).is_empty());
+
+			Utxo::build_owner_index();
+
+			assert!(OwnerIndexBuilt::get());
+			assert_eq!(Utxo::outpoints_of(&alice), vec![genesis_utxo()]);
+			assert_eq!(Utxo::balance_of(&alice), 100);
+		});
+	}
+
+	#[test]
+	fn test_output_format_migration() {
+		new_test_ext().execute_with(|| {
+			let alice = H256::from(sp_io::crypto::sr25519_public_keys(SR25519)[0]);
+			let time_locked = H256::repeat_byte(0x01);
+
+			// pretend the chain was started with outputs of a single key, some of them time locked
+			put_raw(&UtxoStore::hashed_key_for(genesis_utxo()), &(100 as Value, alice).encode());
+			put_raw(&UtxoStore::hashed_key_for(time_locked), &(20 as Value, alice, Some(5 as BlockNumber)).encode());
+			OutputsUpgraded::put(false);
+			OwnerIndexBuilt::put(false);
+			SupplyTracked::put(false);
+			UtxoOwners::remove_prefix(alice);
+
+			Utxo::on_runtime_upgrade();
+
+			assert!(OutputsUpgraded::get());
+			assert_eq!(Utxo::utxo(&genesis_utxo()), Some(genesis_output(alice)));
+			assert_eq!(
+				Utxo::utxo(&time_locked),
+				Some(TransactionOutput { value: 20, lock: Lock::Key(alice), lock_until: Some(5) })
+			);
+			assert_eq!(Utxo::outpoints_of(&alice).len(), 2);
+			assert_eq!(Utxo::total_supply(), 120);
+		});
+	}
 }